@@ -1,18 +1,82 @@
 #![no_std]
 
-use gmeta::{In, InOut, Metadata, Out};
-use gstd::{collections::HashMap, prelude::*, ActorId, MessageId};
-
+use gmeta::{In, InOut, Metadata};
+use gstd::{
+    collections::{HashMap, HashSet},
+    prelude::*,
+    ActorId, MessageId, ReservationId,
+};
 
 #[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
 pub struct GameSessionState {
     pub wordle_program_id: ActorId,
-    pub game_sessions: Vec<(ActorId, SessionInfo)>,
+    pub ft_program_id: Option<ActorId>,
+    pub admin: ActorId,
+    /// 已注册的后端游戏
+    pub games: Vec<(ActorId, GameKind)>,
+    pub config: GameSessionConfig,
+    pub reward_config: RewardConfig,
+    /// 可用于发放奖励的资金
+    pub pool: u128,
+    /// 发放失败、等待重试的奖励
+    pub pending_payouts: Vec<(ActorId, u128)>,
+    /// 正在通过代币程序发放、尚未收到回复的奖励
+    /// 以 (玩家, 会话或重试消息的ID) 为键
+    pub pending_rewards: Vec<((ActorId, MessageId), PendingReward)>,
+    /// 保存的会话数，会话本身用 `StateQuery::Sessions` 分页读取
+    pub session_count: u32,
+    /// 最近的异常回复，按收到的先后顺序
+    pub anomalies: Vec<ReplyAnomaly>,
+    pub races: Vec<(u64, Race)>,
+    /// 尚未结束的挑战：(挑战ID, 挑战者, 对手)，不包含挑战的单词
+    pub challenges: Vec<(MessageId, ActorId, ActorId)>,
+    /// 用于延迟的 CheckGameStatus 消息的 gas 预留，按预留的先后顺序
+    pub reservations: Vec<GasReservation>,
+    /// 暂停时不能开始新的会话
+    pub paused: bool,
+    /// 已导出状态，会话由新程序继续
+    pub migrated: bool,
+    /// 正在导入旧程序的状态，恢复运行时完成
+    pub importing: bool,
+    /// 导入后还没有安排超时检查的会话数
+    pub unscheduled_checks: u32,
+}
+
+/// `GameSessionSnapshot` 的格式版本，格式改变时增加
+pub const STATE_VERSION: u32 = 1;
+
+/// 迁移到新版本程序所需的状态，由 ExportState 分页导出：每页的各个列表只包含从
+/// `offset` 开始的一部分。第一页在 init 中导入，其余各页按顺序用 ImportState 导入。
+/// 导出时没有等待中的回复和奖励发放，gas 预留属于旧程序，不包含在内。
+/// 会话配置和代币程序也不包含在内，需要在新程序的 `GameSessionInit` 中重新设置
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
+pub struct GameSessionSnapshot {
+    pub version: u32,
+    /// 导出状态的程序，进行中的竞速继续使用它在 Wordle 中的单词
+    pub program_id: ActorId,
+    pub games: Vec<(ActorId, GameKind)>,
+    pub reward_config: RewardConfig,
+    pub pending_payouts: Vec<(ActorId, u128)>,
+    pub sessions: Vec<((ActorId, MessageId), SessionInfo)>,
+    /// 每个玩家的归档按结束的先后顺序排列
+    pub archive: Vec<(ActorId, ArchivedSession)>,
+    pub stats: Vec<(ActorId, PlayerStats)>,
+    pub races: Vec<(u64, Race)>,
+    pub next_race_id: u64,
+    pub challenges: Vec<(MessageId, Challenge)>,
 }
 
 #[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
 pub struct GameSessionInit {
     pub wordle_program_id: ActorId,
+    pub config: GameSessionConfig,
+    /// 设置后，胜利奖励以该同质化代币程序中的代币发放，而不是原生代币
+    pub ft_program_id: Option<ActorId>,
+    /// 导入旧版本程序导出状态的第一页，新程序处于暂停状态，其余各页用 ImportState 导入，
+    /// 全部导入后用 Resume 恢复运行。奖池不随快照迁移，以 init 附带的金额作为奖池；
+    /// `config` 和 `ft_program_id` 也不随快照迁移，需要与旧程序的设置一致。
+    /// 新程序需要在 Wordle 中被设为可信程序，才能继续导入的会话
+    pub import: Option<GameSessionSnapshot>,
 }
 
 impl GameSessionInit {
@@ -21,52 +85,601 @@ impl GameSessionInit {
             !self.wordle_program_id.is_zero(),
             "Invalid wordle_program_id"
         );
+        assert!(self.config.is_valid(), "Invalid config");
+        assert!(
+            !matches!(self.ft_program_id, Some(id) if id.is_zero()),
+            "Invalid ft_program_id"
+        );
+        assert!(
+            !matches!(&self.import, Some(snapshot) if snapshot.version != STATE_VERSION),
+            "Unsupported state version"
+        );
     }
 }
 
 impl From<GameSessionInit> for GameSession {
     fn from(game_session_init: GameSessionInit) -> Self {
+        let mut games = HashMap::new();
+        games.insert(game_session_init.wordle_program_id, GameKind::Wordle);
         Self {
             wordle_program_id: game_session_init.wordle_program_id,
+            games,
+            config: game_session_init.config,
+            ft_program_id: game_session_init.ft_program_id,
             ..Default::default()
         }
     }
 }
 
+/// 会话配置，修改后只对新开始的会话生效
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct GameSessionConfig {
+    /// Wordle 会话尝试次数的上限。Wordle 程序无法在配置时查询，需要由管理员保证不超过
+    /// Wordle 的 `max_attempts`，否则 Wordle 会先判定失败，`tries_left` 会多报剩余次数
+    pub tries_limit: u8,
+    /// 游戏开始后经过多少个区块检查是否超时，必须大于 `reply_timeout_blocks`
+    pub timeout_blocks: u32,
+    /// 等待 Wordle 回复的最长区块数，超时后回复 `GameSessionError::WordleUnavailable`
+    pub reply_timeout_blocks: u32,
+    /// 延迟的 CheckGameStatus 消息的 gas 上限，也是每份 gas 预留的数量
+    pub check_status_gas: u64,
+    /// gas 预留的有效区块数
+    pub reservation_blocks: u32,
+    /// 已结束的会话、竞速和未被接受的挑战保留多少个区块后被清理，归档记录和统计数据不受影响
+    pub retention_blocks: u32,
+}
+
+impl Default for GameSessionConfig {
+    fn default() -> Self {
+        Self {
+            tries_limit: 5,
+            timeout_blocks: 200,
+            reply_timeout_blocks: 10,
+            check_status_gas: 1_000_000,
+            reservation_blocks: 100_000,
+            retention_blocks: 14_400,
+        }
+    }
+}
+
+impl GameSessionConfig {
+    /// 会话超时不能早于等待开始回复的超时，否则被唤醒的开始消息会在会话结束后重新开始游戏
+    pub fn is_valid(&self) -> bool {
+        self.tries_limit > 0
+            && self.timeout_blocks > self.reply_timeout_blocks
+            && self.reply_timeout_blocks > 0
+            && self.check_status_gas > 0
+            && self.reservation_blocks > 0
+    }
+}
+
+/// 入场费和奖励设置，默认均为零
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct RewardConfig {
+    /// StartGame 需要附带的金额
+    pub entry_fee: u128,
+    /// 第 i 项为用 i + 1 次猜中的奖励，用更多次猜中按最后一项发放
+    pub win_rewards: Vec<u128>,
+}
+
+impl RewardConfig {
+    /// 猜中所用次数越多，奖励不能越高
+    pub fn is_valid(&self) -> bool {
+        self.win_rewards.windows(2).all(|w| w[0] >= w[1])
+    }
+
+    pub fn reward_for(&self, tries: u8) -> u128 {
+        self.win_rewards
+            .get(usize::from(tries).saturating_sub(1))
+            .or(self.win_rewards.last())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum GameSessionAction {
+    /// 开始一局新游戏，玩家可以同时进行多局
     StartGame,
     CheckWord {
+        session_id: MessageId,
         word: String,
     },
     CheckGameStatus {
         user: ActorId,
         session_id: MessageId,
     },
+    /// 仅管理员可用
+    UpdateConfig(GameSessionConfig),
+    /// 向奖池注入附带的金额，仅管理员可用
+    Fund,
+    /// 仅管理员可用
+    UpdateRewardConfig(RewardConfig),
+    /// 从奖池中取回资金，仅管理员可用
+    Withdraw {
+        amount: u128,
+    },
+    /// 重新发放之前失败的奖励
+    RetryPayout {
+        user: ActorId,
+    },
+    /// 注册后端游戏程序，仅管理员可用
+    RegisterGame {
+        program_id: ActorId,
+        game: GameKind,
+    },
+    /// 移除后端游戏程序，进行中的会话不受影响，仅管理员可用
+    UnregisterGame {
+        program_id: ActorId,
+    },
+    /// 在已注册的后端游戏上开始一局新游戏，StartGame 使用默认的 Wordle 程序
+    StartGameWith {
+        program_id: ActorId,
+    },
+    /// 在会话中进行一步操作，CheckWord 相当于 `Play` 一个 `GameInput::Word`
+    Play {
+        session_id: MessageId,
+        input: GameInput,
+    },
+    /// 放弃会话，游戏以失败结束，入场费不退还
+    Abandon {
+        session_id: MessageId,
+    },
+    /// 放弃会话并在同一个后端游戏上开始新的一局，回复新会话的 StartSuccess
+    Restart {
+        session_id: MessageId,
+    },
+    /// 创建竞速房间，创建者自动加入并负责开始
+    CreateRace,
+    JoinRace {
+        race_id: u64,
+    },
+    /// 关闭房间并通知所有玩家开始，仅创建者可用
+    StartRace {
+        race_id: u64,
+    },
+    /// 在已开始的竞速中开始自己的一局，所有玩家的单词相同
+    StartRaceGame {
+        race_id: u64,
+    },
+    /// 以自己选择的单词挑战另一个玩家，回复 ChallengeSent，以消息ID 作为 challenge_id
+    Challenge {
+        opponent: ActorId,
+        word: String,
+    },
+    /// 对手接受挑战并开始一局，之后与普通会话相同，结果发送给挑战者
+    AcceptChallenge {
+        challenge_id: MessageId,
+    },
+    DeclineChallenge {
+        challenge_id: MessageId,
+    },
+    /// 清理最多 `limit` 个超过保留期的会话、竞速和挑战，仅管理员可用
+    Prune {
+        limit: u32,
+    },
+    /// 用本消息的 gas 预留 `count` 份 `check_status_gas`，用于之后的超时检查，仅管理员可用
+    ReserveGas {
+        count: u32,
+    },
+    /// 取消最多 `count` 份 gas 预留，仅管理员可用
+    UnreserveGas {
+        count: u32,
+    },
+    /// 暂停开始新的会话，进行中的会话和等待中的回复不受影响，仅管理员可用
+    Pause,
+    /// 恢复运行，导入状态的新程序在这时完成导入，仅管理员可用
+    Resume,
+    /// 回复 StateExported，其中的各个列表只包含从 `offset` 开始的最多 `limit` 项，
+    /// `limit` 需要足够小，使回复不超过消息的大小上限。
+    /// 仅在暂停且没有等待中的回复时可用，仅管理员可用。
+    /// 第一次导出后本程序不再接受会话、竞速和挑战的动作，由导入状态的新程序继续
+    ExportState {
+        offset: u32,
+        limit: u32,
+    },
+    /// 为导入后还没有安排超时检查的会话安排检查，最多 `limit` 个，仅管理员可用。
+    /// 玩家在这些会话中的下一个动作也会安排检查
+    ScheduleChecks {
+        limit: u32,
+    },
+    /// 导入旧程序导出状态的下一页，仅在 init 导入了第一页、还没有恢复运行时可用，
+    /// 仅管理员可用
+    ImportState(GameSessionSnapshot),
+}
+
+/// 后端游戏的类型，决定会话如何与后端程序通信
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum GameKind {
+    #[default]
+    Wordle,
+    /// 每局使用给定的设置重新开始
+    Pebbles(PebblesInit),
+}
+
+/// 玩家在会话中的操作
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum GameInput {
+    /// 猜测的单词
+    Word(String),
+    /// 本回合取走的石子数
+    Pebbles(u32),
+}
+
+impl GameKind {
+    /// 后端程序是否能同时进行多局。Pebbles 程序只保存一局游戏，同一时间只能有一个会话
+    pub fn supports_concurrent_sessions(&self) -> bool {
+        matches!(self, GameKind::Wordle)
+    }
+
+    /// 会话结束时让后端结束进行中的游戏的消息，不需要结束的游戏返回 `None`
+    pub fn end_message(&self, player: ActorId) -> Option<Vec<u8>> {
+        match self {
+            GameKind::Wordle => Some(WordleAction::EndGame { user: player }.encode()),
+            GameKind::Pebbles(..) => None,
+        }
+    }
+
+    /// 会话结束后取得答案的消息，没有答案的游戏返回 `None`。
+    /// 后端的游戏需要先通过 `end_message` 结束
+    pub fn reveal_message(&self, player: ActorId) -> Option<Vec<u8>> {
+        match self {
+            GameKind::Wordle => Some(WordleAction::Reveal { user: player }.encode()),
+            GameKind::Pebbles(..) => None,
+        }
+    }
+
+    /// 解码对 `reveal_message` 的回复
+    pub fn decode_answer(&self, mut payload: &[u8]) -> Option<String> {
+        match (self, WordleEvent::decode(&mut payload)) {
+            (GameKind::Wordle, Ok(WordleEvent::Revealed { answer, .. })) => Some(answer),
+            _ => None,
+        }
+    }
+
+    /// 开始游戏时发送给后端程序的消息
+    pub fn start_message(&self, player: ActorId) -> Vec<u8> {
+        match self {
+            GameKind::Wordle => WordleAction::StartGame { user: player }.encode(),
+            GameKind::Pebbles(init) => PebblesAction::Restart {
+                difficulty: init.difficulty,
+                pebbles_count: init.pebbles_count,
+                max_pebbles_per_turn: init.max_pebbles_per_turn,
+            }
+            .encode(),
+        }
+    }
+
+    /// 玩家的操作对应的消息，操作不属于该游戏时返回 `None`
+    pub fn move_message(&self, player: ActorId, input: &GameInput) -> Option<Vec<u8>> {
+        match (self, input) {
+            (GameKind::Wordle, GameInput::Word(word)) => Some(
+                WordleAction::CheckWord {
+                    user: player,
+                    word: word.clone(),
+                }
+                .encode(),
+            ),
+            (GameKind::Pebbles(..), GameInput::Pebbles(count)) => {
+                Some(PebblesAction::Turn(*count).encode())
+            }
+            _ => None,
+        }
+    }
+
+    /// 把后端程序的回复转换为统一的形式，`is_start` 表示是否为开始游戏的回复
+    pub fn decode_reply(&self, mut payload: &[u8], is_start: bool) -> Option<GameReply> {
+        match self {
+            GameKind::Wordle => WordleEvent::decode(&mut payload)
+                .ok()
+                .filter(WordleEvent::is_game_reply)
+                .map(Into::into),
+            // Pebbles 用 PebblesInit 回复 Restart
+            GameKind::Pebbles(..) if is_start => PebblesInit::decode(&mut payload)
+                .ok()
+                .map(|_| GameReply::Started),
+            GameKind::Pebbles(..) => PebblesEvent::decode(&mut payload).ok().map(Into::into),
+        }
+    }
+}
+
+/// 后端游戏回复的统一形式
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum GameReply {
+    Started,
+    /// 游戏继续
+    Moved(MoveResult),
+    Won(MoveResult),
+    /// 后端知道答案时附带答案
+    Lost(MoveResult, Option<String>),
+}
+
+impl GameReply {
+    /// 是否为对玩家操作的回复
+    pub fn is_move_result(&self) -> bool {
+        !matches!(self, GameReply::Started)
+    }
+
+    pub fn move_result(&self) -> Option<&MoveResult> {
+        match self {
+            GameReply::Started => None,
+            GameReply::Moved(result) | GameReply::Won(result) | GameReply::Lost(result, _) => {
+                Some(result)
+            }
+        }
+    }
+}
+
+/// 一步操作的结果
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum MoveResult {
+    Word {
+        correct_positions: Vec<u8>,
+        contained_in_word: Vec<u8>,
+    },
+    Pebbles {
+        pebbles_remaining: u32,
+    },
 }
 
+/// 同质化代币程序（gFT）的动作，发放奖励时使用 Transfer
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum FtAction {
+    Mint(u128),
+    Burn(u128),
+    Transfer {
+        from: ActorId,
+        to: ActorId,
+        amount: u128,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum FtEvent {
+    Transfer {
+        from: ActorId,
+        to: ActorId,
+        amount: u128,
+    },
+}
+
+/// 与 wordle-io 中的 `Action` 编码相同，只包含会话用到的变体
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum WordleAction {
-    StartGame { user: ActorId },
-    CheckWord { user: ActorId, word: String },
+    StartGame {
+        user: ActorId,
+    },
+    CheckWord {
+        user: ActorId,
+        word: String,
+    },
+    /// 取得已结束的游戏的答案
+    #[codec(index = 5)]
+    Reveal {
+        user: ActorId,
+    },
+    /// 同一个 `shared_id` 的游戏使用相同的单词
+    #[codec(index = 6)]
+    StartSharedGame {
+        user: ActorId,
+        shared_id: u64,
+    },
+    /// 以指定的单词开始游戏，单词不在词典中时失败
+    #[codec(index = 7)]
+    StartGameWithWord {
+        user: ActorId,
+        word: String,
+    },
+    /// 删除玩家最近结束的游戏和共用的单词
+    #[codec(index = 8)]
+    Forget {
+        users: Vec<ActorId>,
+        shared_ids: Vec<u64>,
+    },
+    /// 使用另一个程序 `owner` 的共用单词开始游戏
+    #[codec(index = 9)]
+    StartSharedGameOf {
+        user: ActorId,
+        owner: ActorId,
+        shared_id: u64,
+    },
+    /// 把进行中的游戏判负，不返回答案
+    #[codec(index = 10)]
+    EndGame {
+        user: ActorId,
+    },
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum GameSessionEvent {
-    StartSuccess,
+    /// 之后的 CheckWord 需要带上 `session_id`
+    StartSuccess {
+        session_id: MessageId,
+    },
     CheckWordResult {
         correct_positions: Vec<u8>,
         contained_in_word: Vec<u8>,
+        /// 包括本次在内已经用掉的尝试次数
+        tries_used: u8,
+        /// 按会话的 `tries_limit` 计算的剩余尝试次数
+        tries_left: u8,
+        /// 本局所有猜测汇总后的键盘状态
+        keyboard: Keyboard,
+    },
+    /// 会话结束。答案未知时为 `None`，稍后通过 AnswerRevealed 发送。
+    /// 竞速中的会话在竞速结束后才发送答案
+    GameOver {
+        session_id: MessageId,
+        status: GameStatus,
+        answer: Option<String>,
+        board: Vec<BoardRow>,
+    },
+    ConfigUpdated,
+    /// 清理的会话、竞速和挑战数
+    Pruned {
+        sessions: u32,
+        races: u32,
+        challenges: u32,
+    },
+    /// 预留或取消之后可用的 gas 预留份数
+    GasReserved {
+        available: u32,
+    },
+    GasUnreserved {
+        available: u32,
+    },
+    Paused,
+    Resumed,
+    /// `total` 为各个列表中最长的项数，导出到 `offset` 不小于它为止
+    StateExported {
+        total: u32,
+        snapshot: GameSessionSnapshot,
+    },
+    StateImported,
+    /// 还没有安排超时检查的导入会话数
+    ChecksScheduled {
+        remaining: u32,
+    },
+    Funded {
+        pool: u128,
+    },
+    RewardConfigUpdated,
+    /// 随奖励一起发送给玩家
+    RewardPaid {
+        amount: u128,
+    },
+    Withdrawn {
+        amount: u128,
+    },
+    /// 玩家仍未领到的奖励
+    PendingPayout {
+        user: ActorId,
+        amount: u128,
+    },
+    /// Pebbles 程序走完一步后剩余的石子
+    CounterTurn {
+        pebbles_remaining: u32,
+    },
+    GameRegistered {
+        program_id: ActorId,
+    },
+    GameUnregistered {
+        program_id: ActorId,
+    },
+    /// 会话结束后从后端取得的答案
+    AnswerRevealed {
+        session_id: MessageId,
+        answer: String,
+    },
+    /// 动作失败，状态没有改变，附带的金额随该回复退还
+    Error(GameSessionError),
+    RaceCreated {
+        race_id: u64,
+    },
+    /// 房间中现有的玩家数
+    RaceJoined {
+        race_id: u64,
+        players: u32,
+    },
+    /// 发送给房间中的每个玩家，之后用 StartRaceGame 开始
+    RaceStarted {
+        race_id: u64,
+    },
+    /// 有玩家先猜中，发送给其他玩家，他们仍在进行的会话以失败结束
+    RaceFinished {
+        race_id: u64,
+        winner: ActorId,
+    },
+    ChallengeSent {
+        challenge_id: MessageId,
+    },
+    /// 发送给被挑战的玩家
+    ChallengeReceived {
+        challenge_id: MessageId,
+        challenger: ActorId,
+    },
+    /// 回复对手，同时发送给挑战者
+    ChallengeDeclined {
+        challenge_id: MessageId,
+    },
+    /// 对手的会话结束，发送给挑战者，`status` 为对手的结果
+    ChallengeFinished {
+        challenge_id: MessageId,
+        opponent: ActorId,
+        status: GameStatus,
     },
-    GameOver(GameStatus),
 }
 
-#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+/// 动作失败的原因，由前端自行翻译成提示信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum GameSessionError {
+    /// 会话不存在或已经结束
+    NotInGame,
+    /// 会话已经开始
+    AlreadyInGame,
+    /// 正在等待后端游戏的回复
+    ReplyPending,
+    /// 单词不是五个小写字母
+    InvalidWord,
+    /// 后端游戏不支持该输入
+    UnsupportedInput,
+    /// 后端游戏没有在期限内回复，玩家可以重新发送请求
+    WordleUnavailable,
+    /// 后端游戏未注册
+    GameNotRegistered,
+    /// 后端游戏不支持并发会话，且正在被其他会话使用
+    GameBusy,
+    /// 附带的金额与入场费不符
+    WrongEntryFee,
+    NotAdmin,
+    InvalidConfig,
+    InvalidProgramId,
+    /// 不能移除默认的 Wordle 程序
+    CannotUnregisterWordle,
+    /// 提取的金额超过奖池余额
+    InsufficientPool,
+    RaceNotFound,
+    /// 竞速已经开始，不能再加入
+    RaceNotOpen,
+    /// 竞速尚未开始或已经结束
+    RaceNotRunning,
+    /// 玩家没有加入该竞速
+    NotInRace,
+    AlreadyInRace,
+    /// 房间已满 `MAX_RACE_PLAYERS` 人
+    RaceFull,
+    NotRaceHost,
+    /// 挑战不存在、已被接受或不是发给该玩家的
+    ChallengeNotFound,
+    /// 不能挑战自己
+    InvalidOpponent,
+    /// 已暂停，不能开始新的会话
+    Paused,
+    /// 只能在暂停时导出状态
+    NotPaused,
+    /// 挑战的单词不在 Wordle 的词库中
+    WordNotInDictionary,
+    /// 玩家创建的竞速还没有开始
+    RaceAlreadyOpen,
+    /// 未被接受的挑战已有 `MAX_PENDING_CHALLENGES` 个
+    TooManyChallenges,
+    /// 状态已导出到新程序
+    Migrated,
+    /// 还在导入旧程序的状态，恢复运行后才能继续
+    Importing,
+    /// 没有正在进行的导入
+    NotImporting,
+    /// 导入的状态版本不支持，或者与之前导入的各页不是来自同一个程序
+    InvalidSnapshot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
 pub enum GameStatus {
     Win,
     Lose,
 }
 
+/// 与 wordle-io 中的 `Event` 编码相同，只包含会话用到的变体
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum WordleEvent {
     GameStarted {
@@ -77,6 +690,20 @@ pub enum WordleEvent {
         correct_positions: Vec<u8>,
         contained_in_word: Vec<u8>,
     },
+    GameWon {
+        user: ActorId,
+    },
+    GameLost {
+        user: ActorId,
+        correct_positions: Vec<u8>,
+        contained_in_word: Vec<u8>,
+        answer: String,
+    },
+    #[codec(index = 6)]
+    Revealed {
+        user: ActorId,
+        answer: String,
+    },
 }
 
 impl WordleEvent {
@@ -84,35 +711,143 @@ impl WordleEvent {
         match self {
             WordleEvent::GameStarted { user } => user,
             WordleEvent::WordChecked { user, .. } => user,
+            WordleEvent::GameWon { user } => user,
+            WordleEvent::GameLost { user, .. } => user,
+            WordleEvent::Revealed { user, .. } => user,
         }
     }
 
     pub fn has_guessed(&self) -> bool {
         match self {
-            WordleEvent::GameStarted { .. } => unimplemented!(),
+            WordleEvent::GameStarted { .. } => false,
             WordleEvent::WordChecked {
                 correct_positions, ..
             } => correct_positions == &vec![0, 1, 2, 3, 4],
+            WordleEvent::GameWon { .. } => true,
+            WordleEvent::GameLost { .. } | WordleEvent::Revealed { .. } => false,
         }
     }
-}
 
-impl From<&WordleEvent> for GameSessionEvent {
-    fn from(wordle_event: &WordleEvent) -> Self {
-        match wordle_event {
-            WordleEvent::GameStarted { .. } => GameSessionEvent::StartSuccess,
+    pub fn has_lost(&self) -> bool {
+        matches!(self, WordleEvent::GameLost { .. })
+    }
+
+    /// 猜测结果：位置正确的字母和包含在单词中的字母
+    pub fn guess_result(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        match self {
+            WordleEvent::GameStarted { .. } | WordleEvent::Revealed { .. } => None,
             WordleEvent::WordChecked {
                 correct_positions,
                 contained_in_word,
                 ..
-            } => GameSessionEvent::CheckWordResult {
-                correct_positions: correct_positions.clone(),
-                contained_in_word: contained_in_word.clone(),
-            },
+            }
+            | WordleEvent::GameLost {
+                correct_positions,
+                contained_in_word,
+                ..
+            } => Some((correct_positions.clone(), contained_in_word.clone())),
+            WordleEvent::GameWon { .. } => Some((vec![0, 1, 2, 3, 4], vec![])),
+        }
+    }
+
+    /// 是否为对猜测单词的回复
+    pub fn is_guess_result(&self) -> bool {
+        matches!(
+            self,
+            WordleEvent::WordChecked { .. }
+                | WordleEvent::GameWon { .. }
+                | WordleEvent::GameLost { .. }
+        )
+    }
+
+    /// 是否为对 StartGame 或 CheckWord 的回复
+    pub fn is_game_reply(&self) -> bool {
+        !matches!(self, WordleEvent::Revealed { .. })
+    }
+}
+
+impl From<WordleEvent> for GameReply {
+    fn from(wordle_event: WordleEvent) -> Self {
+        let Some((correct_positions, contained_in_word)) = wordle_event.guess_result() else {
+            return GameReply::Started;
+        };
+        let result = MoveResult::Word {
+            correct_positions,
+            contained_in_word,
+        };
+        let has_guessed = wordle_event.has_guessed();
+        match wordle_event {
+            WordleEvent::GameLost { answer, .. } => GameReply::Lost(result, Some(answer)),
+            _ if has_guessed => GameReply::Won(result),
+            _ => GameReply::Moved(result),
+        }
+    }
+}
+
+// 以下类型与 pebbles-game-io 中的同名类型编码相同
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PebblesInit {
+    pub difficulty: DifficultyLevel,
+    pub pebbles_count: u32,
+    pub max_pebbles_per_turn: u32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum DifficultyLevel {
+    #[default]
+    Easy,
+    Hard,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum PebblesAction {
+    Turn(u32),
+    GiveUp,
+    Restart {
+        difficulty: DifficultyLevel,
+        pebbles_count: u32,
+        max_pebbles_per_turn: u32,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum PebblesEvent {
+    CounterTurn(u32),
+    Won(PebblesPlayer),
+}
+
+/// 即 pebbles-game-io 中的 `Player`
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum PebblesPlayer {
+    User,
+    Program,
+}
+
+impl From<PebblesEvent> for GameReply {
+    fn from(pebbles_event: PebblesEvent) -> Self {
+        let finished = MoveResult::Pebbles {
+            pebbles_remaining: 0,
+        };
+        match pebbles_event {
+            PebblesEvent::CounterTurn(pebbles_remaining) => {
+                GameReply::Moved(MoveResult::Pebbles { pebbles_remaining })
+            }
+            // 取走最后一颗石子的一方获胜
+            PebblesEvent::Won(PebblesPlayer::User) => GameReply::Won(finished),
+            PebblesEvent::Won(PebblesPlayer::Program) => GameReply::Lost(finished, None),
         }
     }
 }
 
+/// 会话在 Wordle 中使用的玩家ID。
+///
+/// Wordle 为每个玩家只保存一局游戏，因此每个会话以 `session_id` 作为独立的玩家，
+/// 同一个玩家的多个会话互不影响。
+pub fn wordle_player(session_id: MessageId) -> ActorId {
+    ActorId::new(session_id.into())
+}
+
 #[derive(Default, Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum SessionStatus {
     #[default]
@@ -120,20 +855,450 @@ pub enum SessionStatus {
     WaitUserInput,
     WaitWordleStartReply,
     WaitWordleCheckWordReply,
-    ReplyReceived(WordleEvent),
+    ReplyReceived(GameReply),
     GameOver(GameStatus),
 }
 
 #[derive(Default, Debug, Clone, Encode, Decode, TypeInfo)]
 pub struct SessionInfo {
     pub session_id: MessageId,
+    pub game: GameKind,
+    /// 后端游戏程序
+    pub game_program_id: ActorId,
     pub original_msg_id: MessageId,
     pub send_to_wordle_msg_id: MessageId,
     pub tries: u8,
+    /// 会话开始时的尝试次数上限，0 表示不限制，由后端游戏判定结束
+    pub tries_limit: u8,
     pub session_status: SessionStatus,
+    /// 会话开始时的区块高度
+    pub start_block: u32,
+    /// 已发送给 Wordle、尚未收到结果的单词
+    pub pending_word: Option<String>,
+    pub guesses: Vec<GuessRecord>,
+    /// 由 `guesses` 汇总的键盘状态
+    pub keyboard: Keyboard,
+    /// 会话所属的竞速
+    pub race_id: Option<u64>,
+    /// 会话所接受的挑战
+    pub challenge_id: Option<MessageId>,
+    /// 会话结束时的区块高度，用于计算保留期
+    pub end_block: u32,
+}
+
+/// 程序预留的一份 gas，用于发送延迟消息，不依赖玩家消息的 gas
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct GasReservation {
+    pub id: ReservationId,
+    pub amount: u64,
+    /// 在该区块高度到期，之后不能再使用
+    pub expires_at: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct PendingReward {
+    /// 发送给代币程序的 Transfer 消息ID
+    pub msg_id: MessageId,
+    pub amount: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct GuessRecord {
+    pub word: String,
+    pub correct_positions: Vec<u8>,
+    pub contained_in_word: Vec<u8>,
+}
+
+/// 字母在答案中的状态，按已知信息的多少排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, TypeInfo)]
+pub enum LetterState {
+    /// 不在单词中
+    Absent,
+    /// 在单词中，但位置不对
+    Present,
+    /// 位置正确
+    Correct,
+}
+
+/// 棋盘上的一行，即一次猜测中每个字母的结果
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct BoardRow {
+    pub word: String,
+    pub letters: Vec<LetterState>,
+}
+
+impl From<&GuessRecord> for BoardRow {
+    fn from(guess: &GuessRecord) -> Self {
+        let letters = (0..guess.word.len() as u8)
+            .map(|i| {
+                if guess.correct_positions.contains(&i) {
+                    LetterState::Correct
+                } else if guess.contained_in_word.contains(&i) {
+                    LetterState::Present
+                } else {
+                    LetterState::Absent
+                }
+            })
+            .collect();
+        Self {
+            word: guess.word.clone(),
+            letters,
+        }
+    }
+}
+
+/// 键盘上每个字母 a–z 目前已知的最好状态，没有猜过的字母为 `None`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Keyboard(pub [Option<LetterState>; 26]);
+
+impl Keyboard {
+    /// 用一次猜测的结果更新键盘，每个字母只会变得更确定
+    pub fn record(&mut self, row: &BoardRow) {
+        for (letter, state) in row.word.bytes().zip(&row.letters) {
+            if let Some(key) = letter
+                .checked_sub(b'a')
+                .and_then(|index| self.0.get_mut(usize::from(index)))
+            {
+                *key = (*key).max(Some(*state));
+            }
+        }
+    }
+
+    pub fn get(&self, letter: char) -> Option<LetterState> {
+        let index = u32::from(letter).checked_sub(u32::from('a'))?;
+        self.0.get(index as usize).copied().flatten()
+    }
+}
+
+/// 最多保留的异常回复数量
+pub const MAX_ANOMALIES: usize = 100;
+
+/// handle_reply 收到的、没有被正常处理的回复，供运维人员排查
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct ReplyAnomaly {
+    pub reply_to: MessageId,
+    pub source: ActorId,
+    /// 收到回复时的区块高度
+    pub block: u32,
+    pub kind: AnomalyKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum AnomalyKind {
+    /// 不是对正在等待回复的消息的回复，例如超时之后才到达的回复
+    UnknownMessage,
+    /// 不是来自消息的接收者
+    UnexpectedSource,
+    /// 后端程序执行失败
+    ErrorReply,
+    /// 无法解码
+    Undecodable,
+    /// 与会话当前等待的回复类型不符
+    UnexpectedReply,
+}
+
+/// 已结束的会话
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct ArchivedSession {
+    pub session_id: MessageId,
+    pub start_block: u32,
+    pub end_block: u32,
+    pub guesses: Vec<GuessRecord>,
+    pub tries: u8,
+    pub status: GameStatus,
+    pub score: u32,
+    /// 答案，在会话结束后才从后端取得时可能暂时为 `None`
+    pub answer: Option<String>,
+}
+
+/// 按键排序后取出从 `offset` 开始的最多 `limit` 项
+fn page<K: Copy + Ord + core::hash::Hash, V: Clone>(
+    map: &HashMap<K, V>,
+    offset: u32,
+    limit: u32,
+) -> Vec<(K, V)> {
+    let mut keys: Vec<_> = map.keys().copied().collect();
+    keys.sort();
+    keys.into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|key| (key, map[&key].clone()))
+        .collect()
+}
+
+/// 胜利的得分：尝试次数越少、从开始到猜中经过的区块越少，得分越高。
+/// 失败不得分。`tries_limit` 为 0 时不限制尝试次数，只按区块计分。
+pub fn win_score(tries: u8, tries_limit: u8, blocks_taken: u32) -> u32 {
+    let tries_score = match tries_limit {
+        0 => 0,
+        limit => 100 * (u32::from(limit) + 1 - u32::from(tries.clamp(1, limit))),
+    };
+    let time_score = 100u32.saturating_sub(blocks_taken);
+    tries_score + time_score
+}
+
+/// 玩家在 Wordle 中的统计数据，与 Wordle 的统计界面相同
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PlayerStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub current_streak: u32,
+    pub max_streak: u32,
+    /// 用 1–5 次猜中的局数，第 5 项也包含用更多次猜中的局
+    pub guess_distribution: [u32; 5],
+    pub total_score: u64,
+}
+
+impl PlayerStats {
+    pub fn record(&mut self, session: &ArchivedSession) {
+        match session.status {
+            GameStatus::Win => {
+                self.wins += 1;
+                self.current_streak += 1;
+                self.max_streak = self.max_streak.max(self.current_streak);
+                let index = usize::from(session.tries.clamp(1, 5)) - 1;
+                self.guess_distribution[index] += 1;
+            }
+            GameStatus::Lose => {
+                self.losses += 1;
+                self.current_streak = 0;
+            }
+        }
+        self.total_score += u64::from(session.score);
+    }
+}
+
+/// 会话在某个状态下收到某个动作时应执行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// 向 Wordle 发送 StartGame 并等待回复
+    StartGame,
+    /// 已收到 StartGame 的回复，通知玩家游戏开始
+    GameStarted,
+    /// 向 Wordle 发送猜测的单词并等待回复
+    SendGuess,
+    /// 已收到猜测结果，计入尝试次数并回复玩家
+    GuessChecked,
+    /// 等待 Wordle 回复超时，恢复到请求之前的状态
+    WordleUnavailable,
+    /// 超时，游戏以失败结束
+    Timeout,
+    /// 玩家放弃，游戏以失败结束
+    Abandon,
+    /// 忽略该动作
+    Ignore,
+    /// 当前状态下不允许该动作
+    Reject(GameSessionError),
+}
+
+impl SessionStatus {
+    /// 状态转换表，覆盖所有 `SessionStatus` × `GameSessionAction` 的组合。
+    ///
+    /// `is_waiting_msg` 表示当前消息是否为正在等待 Wordle 回复的那条消息；
+    /// 只有它被唤醒后才能处理 `ReplyReceived` 中的回复。它在仍处于等待状态时
+    /// 被执行，说明等待已超时。
+    pub fn transition(&self, action: &GameSessionAction, is_waiting_msg: bool) -> Transition {
+        use GameSessionAction as A;
+        use SessionStatus as S;
+
+        match (self, action) {
+            // 被唤醒的开始消息对应的会话已经结束，不能再开始一局
+            (
+                S::Init | S::GameOver(..),
+                A::StartGame
+                | A::StartGameWith { .. }
+                | A::StartRaceGame { .. }
+                | A::AcceptChallenge { .. },
+            ) if is_waiting_msg => Transition::Reject(GameSessionError::NotInGame),
+            (
+                S::Init | S::GameOver(..),
+                A::StartGame
+                | A::StartGameWith { .. }
+                | A::StartRaceGame { .. }
+                | A::AcceptChallenge { .. },
+            ) => Transition::StartGame,
+            (
+                S::WaitUserInput | S::WaitWordleCheckWordReply,
+                A::StartGame
+                | A::StartGameWith { .. }
+                | A::StartRaceGame { .. }
+                | A::AcceptChallenge { .. },
+            ) => Transition::Reject(GameSessionError::AlreadyInGame),
+            (
+                S::WaitWordleStartReply,
+                A::StartGame
+                | A::StartGameWith { .. }
+                | A::StartRaceGame { .. }
+                | A::AcceptChallenge { .. },
+            ) if is_waiting_msg => Transition::WordleUnavailable,
+            (
+                S::WaitWordleStartReply,
+                A::StartGame
+                | A::StartGameWith { .. }
+                | A::StartRaceGame { .. }
+                | A::AcceptChallenge { .. }
+                | A::CheckWord { .. }
+                | A::Play { .. },
+            ) => Transition::Reject(GameSessionError::ReplyPending),
+            (
+                S::ReplyReceived(reply),
+                A::StartGame
+                | A::StartGameWith { .. }
+                | A::StartRaceGame { .. }
+                | A::AcceptChallenge { .. },
+            ) => {
+                if is_waiting_msg && !reply.is_move_result() {
+                    Transition::GameStarted
+                } else {
+                    Transition::Reject(GameSessionError::ReplyPending)
+                }
+            }
+            (S::WaitUserInput, A::CheckWord { .. } | A::Play { .. }) => Transition::SendGuess,
+            (S::WaitWordleCheckWordReply, A::CheckWord { .. } | A::Play { .. }) => {
+                if is_waiting_msg {
+                    Transition::WordleUnavailable
+                } else {
+                    Transition::Reject(GameSessionError::ReplyPending)
+                }
+            }
+            (S::ReplyReceived(reply), A::CheckWord { .. } | A::Play { .. }) => {
+                if is_waiting_msg && reply.is_move_result() {
+                    Transition::GuessChecked
+                } else {
+                    Transition::Reject(GameSessionError::ReplyPending)
+                }
+            }
+            (S::Init | S::GameOver(..), A::CheckWord { .. } | A::Play { .. }) => {
+                Transition::Reject(GameSessionError::NotInGame)
+            }
+            (S::WaitUserInput, A::Abandon { .. } | A::Restart { .. }) => Transition::Abandon,
+            (
+                S::WaitWordleStartReply | S::WaitWordleCheckWordReply | S::ReplyReceived(..),
+                A::Abandon { .. } | A::Restart { .. },
+            ) => Transition::Reject(GameSessionError::ReplyPending),
+            (S::Init | S::GameOver(..), A::Abandon { .. } | A::Restart { .. }) => {
+                Transition::Reject(GameSessionError::NotInGame)
+            }
+            (S::Init | S::GameOver(..), A::CheckGameStatus { .. }) => Transition::Ignore,
+            (
+                S::WaitUserInput
+                | S::WaitWordleStartReply
+                | S::WaitWordleCheckWordReply
+                | S::ReplyReceived(..),
+                A::CheckGameStatus { .. },
+            ) => Transition::Timeout,
+            // 管理员和奖励相关的动作不属于会话
+            (
+                _,
+                A::UpdateConfig(..)
+                | A::Prune { .. }
+                | A::ReserveGas { .. }
+                | A::UnreserveGas { .. }
+                | A::Pause
+                | A::Resume
+                | A::ExportState { .. }
+                | A::ImportState(..)
+                | A::ScheduleChecks { .. }
+                | A::Fund
+                | A::UpdateRewardConfig(..)
+                | A::Withdraw { .. }
+                | A::RetryPayout { .. }
+                | A::RegisterGame { .. }
+                | A::UnregisterGame { .. }
+                | A::CreateRace
+                | A::JoinRace { .. }
+                | A::StartRace { .. }
+                | A::Challenge { .. }
+                | A::DeclineChallenge { .. },
+            ) => Transition::Ignore,
+        }
+    }
+
+    /// 当前状态是否在等待该回复
+    pub fn expects_reply(&self, reply: &GameReply) -> bool {
+        match self {
+            SessionStatus::WaitWordleStartReply => !reply.is_move_result(),
+            SessionStatus::WaitWordleCheckWordReply => reply.is_move_result(),
+            _ => false,
+        }
+    }
 }
 
 impl SessionInfo {
+    /// 开始游戏时发送给后端程序的消息，竞速中的会话共用一个单词
+    pub fn start_message(&self, player: ActorId) -> Vec<u8> {
+        match (self.game, self.race_id) {
+            (GameKind::Wordle, Some(race_id)) => WordleAction::StartSharedGame {
+                user: player,
+                shared_id: race_id,
+            }
+            .encode(),
+            (game, _) => game.start_message(player),
+        }
+    }
+
+    /// 记录一次猜测并更新键盘状态
+    pub fn record_guess(&mut self, guess: GuessRecord) {
+        self.keyboard.record(&BoardRow::from(&guess));
+        self.guesses.push(guess);
+    }
+
+    /// 游戏没有结束时，一步操作的结果回复给玩家的事件
+    pub fn move_event(&self, result: MoveResult) -> GameSessionEvent {
+        match result {
+            MoveResult::Word {
+                correct_positions,
+                contained_in_word,
+            } => GameSessionEvent::CheckWordResult {
+                correct_positions,
+                contained_in_word,
+                tries_used: self.tries,
+                tries_left: self.tries_limit.saturating_sub(self.tries),
+                keyboard: self.keyboard.clone(),
+            },
+            MoveResult::Pebbles { pebbles_remaining } => {
+                GameSessionEvent::CounterTurn { pebbles_remaining }
+            }
+        }
+    }
+
+    /// 结束会话，返回用于归档的记录
+    pub fn finish(
+        &mut self,
+        status: GameStatus,
+        answer: Option<String>,
+        end_block: u32,
+    ) -> ArchivedSession {
+        self.session_status = SessionStatus::GameOver(status.clone());
+        self.end_block = end_block;
+        let score = match status {
+            GameStatus::Win => win_score(
+                self.tries,
+                self.tries_limit,
+                end_block.saturating_sub(self.start_block),
+            ),
+            GameStatus::Lose => 0,
+        };
+        ArchivedSession {
+            session_id: self.session_id,
+            start_block: self.start_block,
+            end_block,
+            guesses: self.guesses.clone(),
+            tries: self.tries,
+            status,
+            score,
+            answer,
+        }
+    }
+
+    /// 会话是否已开始且尚未结束
+    pub fn is_active(&self) -> bool {
+        !matches!(
+            self.session_status,
+            SessionStatus::Init | SessionStatus::GameOver(..)
+        )
+    }
+
     pub fn is_wait_reply_status(&self) -> bool {
         matches!(
             self.session_status,
@@ -142,21 +1307,369 @@ impl SessionInfo {
     }
 }
 
+/// 一个玩家向另一个玩家发起的挑战
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct Challenge {
+    pub challenger: ActorId,
+    pub opponent: ActorId,
+    /// 挑战者选择的单词，创建时已按词典校验
+    pub word: String,
+    /// 对手接受后开始的会话
+    pub session_id: Option<MessageId>,
+    /// 创建时的区块高度，超过保留期仍未被接受的挑战被清理
+    pub created_block: u32,
+}
+
+/// 一个竞速房间最多的玩家数
+pub const MAX_RACE_PLAYERS: usize = 16;
+
+/// 每个玩家最多同时发出的未被接受的挑战数
+pub const MAX_PENDING_CHALLENGES: usize = 16;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum RaceStatus {
+    /// 等待玩家加入
+    #[default]
+    Open,
+    Running,
+    Finished {
+        winner: ActorId,
+    },
+}
+
+/// 竞速房间：加入的玩家猜同一个单词，先猜中的获胜
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
+pub struct Race {
+    pub host: ActorId,
+    /// 按加入的先后顺序，第一个为创建者
+    pub players: Vec<ActorId>,
+    /// 玩家在竞速中开始的会话
+    pub sessions: Vec<(ActorId, MessageId)>,
+    pub status: RaceStatus,
+    /// 创建、开始或结束时的区块高度，之后超过保留期且没有进行中的会话时被清理
+    pub last_block: u32,
+    /// 单词由哪个程序在 Wordle 中取得，为空时是本程序。从旧程序迁移的竞速继续使用旧程序的单词
+    pub word_owner: Option<ActorId>,
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct GameSession {
     pub wordle_program_id: ActorId,
-    pub sessions: HashMap<ActorId, SessionInfo>,
+    pub ft_program_id: Option<ActorId>,
+    pub admin: ActorId,
+    /// 已注册的后端游戏，Wordle 程序在初始化时注册
+    pub games: HashMap<ActorId, GameKind>,
+    pub config: GameSessionConfig,
+    pub reward_config: RewardConfig,
+    pub pool: u128,
+    pub pending_payouts: HashMap<ActorId, u128>,
+    /// 正在发放的奖励，以 (玩家, 会话或重试消息的ID) 为键，同一玩家的多个会话互不影响
+    pub pending_rewards: HashMap<(ActorId, MessageId), PendingReward>,
+    /// 发送给代币程序的 Transfer 消息ID 对应的奖励
+    pub reward_messages: HashMap<MessageId, (ActorId, MessageId)>,
+    /// 以 (玩家, session_id) 为键
+    pub sessions: HashMap<(ActorId, MessageId), SessionInfo>,
+    /// 发送给后端游戏、尚未收到回复的消息ID 对应的会话
+    pub game_messages: HashMap<MessageId, (ActorId, MessageId)>,
+    /// 请求答案的消息ID 对应的已结束会话
+    pub reveal_messages: HashMap<MessageId, (ActorId, MessageId)>,
+    /// 每个玩家已结束的会话，按结束的先后顺序
+    pub archive: HashMap<ActorId, Vec<ArchivedSession>>,
+    pub stats: HashMap<ActorId, PlayerStats>,
+    pub anomalies: Vec<ReplyAnomaly>,
+    pub races: HashMap<u64, Race>,
+    /// 下一个竞速房间的编号
+    pub next_race_id: u64,
+    pub challenges: HashMap<MessageId, Challenge>,
+    pub reservations: Vec<GasReservation>,
+    pub paused: bool,
+    /// 发送给 Wordle 的 Forget 和 EndGame 消息ID，回复无需处理
+    pub cleanup_messages: HashSet<MessageId>,
+    /// 已导出状态，会话由新程序继续
+    pub migrated: bool,
+    /// 正在导入的状态来自的旧程序，恢复运行时完成导入
+    pub importing: Option<ActorId>,
+    /// 导入后还没有安排超时检查的会话
+    pub unscheduled_checks: HashSet<(ActorId, MessageId)>,
+}
+
+impl GameSession {
+    /// 导出迁移所需状态的一页，`program_id` 为本程序。各个列表按键排序后取出从
+    /// `offset` 开始的最多 `limit` 项，导出期间状态不变，因此各页互不重叠。
+    /// 同时返回各个列表中最长的项数
+    pub fn snapshot(
+        &self,
+        program_id: ActorId,
+        offset: u32,
+        limit: u32,
+    ) -> (u32, GameSessionSnapshot) {
+        let mut players: Vec<_> = self.archive.keys().copied().collect();
+        players.sort();
+        let archive = players
+            .into_iter()
+            .flat_map(|player| self.archive[&player].iter().map(move |s| (player, s)));
+        let total = [
+            self.games.len(),
+            self.pending_payouts.len(),
+            self.sessions.len(),
+            self.archive.values().map(Vec::len).sum::<usize>(),
+            self.stats.len(),
+            self.races.len(),
+            self.challenges.len(),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default();
+        let snapshot = GameSessionSnapshot {
+            version: STATE_VERSION,
+            program_id,
+            games: page(&self.games, offset, limit),
+            reward_config: self.reward_config.clone(),
+            pending_payouts: page(&self.pending_payouts, offset, limit),
+            sessions: page(&self.sessions, offset, limit),
+            archive: archive
+                .skip(offset as usize)
+                .take(limit as usize)
+                .map(|(player, s)| (player, s.clone()))
+                .collect(),
+            stats: page(&self.stats, offset, limit),
+            races: page(&self.races, offset, limit),
+            next_race_id: self.next_race_id,
+            challenges: page(&self.challenges, offset, limit),
+        };
+        (total as u32, snapshot)
+    }
+
+    /// 导入旧程序导出的一页状态，各页导入后用 `finish_import` 完成导入
+    pub fn import(&mut self, snapshot: GameSessionSnapshot) {
+        self.importing = Some(snapshot.program_id);
+        self.games.extend(snapshot.games);
+        self.reward_config = snapshot.reward_config;
+        self.pending_payouts.extend(snapshot.pending_payouts);
+        self.sessions.extend(snapshot.sessions);
+        for (player, session) in snapshot.archive {
+            self.archive.entry(player).or_default().push(session);
+        }
+        self.stats.extend(snapshot.stats);
+        self.races.extend(snapshot.races);
+        self.next_race_id = snapshot.next_race_id;
+        self.challenges.extend(snapshot.challenges);
+    }
+
+    /// 完成导入。等待回复的消息不会被迁移：还没有开始的会话被丢弃，
+    /// 等待操作结果的会话恢复为等待玩家输入，该次操作需要重新提交
+    pub fn finish_import(&mut self) {
+        let Some(program_id) = self.importing.take() else {
+            return;
+        };
+        self.sessions.retain(|_, session_info| {
+            match session_info.session_status {
+                SessionStatus::WaitWordleStartReply => return false,
+                SessionStatus::WaitWordleCheckWordReply | SessionStatus::ReplyReceived(..) => {
+                    session_info.pending_word = None;
+                    session_info.session_status = SessionStatus::WaitUserInput;
+                }
+                _ => {}
+            }
+            true
+        });
+        // 已经有玩家开始的竞速在 Wordle 中的单词属于旧程序
+        for race in self.races.values_mut() {
+            if race.status == RaceStatus::Running && !race.sessions.is_empty() {
+                race.word_owner.get_or_insert(program_id);
+            }
+        }
+        // 接受后没能开始的挑战可以重新接受
+        for challenge in self.challenges.values_mut() {
+            let is_started = challenge.session_id.is_some_and(|session_id| {
+                self.sessions
+                    .contains_key(&(challenge.opponent, session_id))
+            });
+            if !is_started {
+                challenge.session_id = None;
+            }
+        }
+        // 旧程序安排的超时检查不会发给新程序
+        self.unscheduled_checks = self
+            .sessions
+            .iter()
+            .filter(|(_, session_info)| session_info.is_active())
+            .map(|(key, _)| *key)
+            .collect();
+    }
+
+    /// 开始会话时发送给后端游戏的消息，接受挑战的会话使用挑战者选择的单词
+    pub fn start_message(&self, key: (ActorId, MessageId), player: ActorId) -> Vec<u8> {
+        let session_info = &self.sessions[&key];
+        match session_info
+            .challenge_id
+            .and_then(|challenge_id| self.challenges.get(&challenge_id))
+        {
+            Some(challenge) => WordleAction::StartGameWithWord {
+                user: player,
+                word: challenge.word.clone(),
+            }
+            .encode(),
+            None => match session_info
+                .race_id
+                .and_then(|race_id| Some((race_id, self.races.get(&race_id)?.word_owner?)))
+            {
+                Some((race_id, owner)) if session_info.game == GameKind::Wordle => {
+                    WordleAction::StartSharedGameOf {
+                        user: player,
+                        owner,
+                        shared_id: race_id,
+                    }
+                    .encode()
+                }
+                _ => session_info.start_message(player),
+            },
+        }
+    }
+
+    /// 取出一份未到期的 gas 预留，同时丢弃已到期的
+    pub fn take_reservation(&mut self, now: u32) -> Option<GasReservation> {
+        self.reservations
+            .retain(|reservation| reservation.expires_at > now);
+        (!self.reservations.is_empty()).then(|| self.reservations.remove(0))
+    }
+
+    /// 记录异常回复，只保留最近的 `MAX_ANOMALIES` 条
+    pub fn record_anomaly(&mut self, anomaly: ReplyAnomaly) {
+        if self.anomalies.len() >= MAX_ANOMALIES {
+            self.anomalies.remove(0);
+        }
+        self.anomalies.push(anomaly);
+    }
+
+    /// 归档已结束的会话并更新玩家的统计数据。
+    /// 统计数据和排行榜只计入 Wordle 的会话，其他游戏的尝试次数不是猜测次数
+    pub fn archive_session(&mut self, user: ActorId, session: ArchivedSession, game: GameKind) {
+        if game == GameKind::Wordle {
+            self.stats.entry(user).or_default().record(&session);
+        }
+        self.archive.entry(user).or_default().push(session);
+    }
+
+    /// 清理超过保留期的会话，最多 `limit` 个，`user` 不为空时只清理该玩家的。
+    /// 仍在等待答案的会话要等收到答案后才能清理。返回清理的会话
+    pub fn prune_sessions(
+        &mut self,
+        now: u32,
+        user: Option<ActorId>,
+        limit: u32,
+    ) -> Vec<((ActorId, MessageId), SessionInfo)> {
+        let retention_blocks = self.config.retention_blocks;
+        let expired: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|((player, _), session_info)| {
+                (user.is_none() || user == Some(*player))
+                    && matches!(session_info.session_status, SessionStatus::GameOver(..))
+                    && session_info.end_block.saturating_add(retention_blocks) <= now
+            })
+            .map(|(key, _)| *key)
+            .filter(|key| !self.reveal_messages.values().any(|k| k == key))
+            .take(limit as usize)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|key| self.sessions.remove_entry(&key))
+            .collect()
+    }
+
+    /// 清理超过保留期且没有进行中的会话的竞速，最多 `limit` 个。返回清理的竞速
+    pub fn prune_races(&mut self, now: u32, limit: u32) -> Vec<(u64, Race)> {
+        let retention_blocks = self.config.retention_blocks;
+        let expired: Vec<_> = self
+            .races
+            .iter()
+            .filter(|(_, race)| {
+                race.last_block.saturating_add(retention_blocks) <= now
+                    && !race
+                        .sessions
+                        .iter()
+                        .any(|key| self.sessions.get(key).is_some_and(SessionInfo::is_active))
+            })
+            .map(|(race_id, _)| *race_id)
+            .take(limit as usize)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|race_id| self.races.remove_entry(&race_id))
+            .collect()
+    }
+
+    /// 清理超过保留期仍未被接受的挑战，最多 `limit` 个。返回清理的挑战数
+    pub fn prune_challenges(&mut self, now: u32, limit: u32) -> u32 {
+        let retention_blocks = self.config.retention_blocks;
+        let expired: Vec<_> = self
+            .challenges
+            .iter()
+            .filter(|(_, challenge)| {
+                challenge.session_id.is_none()
+                    && challenge.created_block.saturating_add(retention_blocks) <= now
+            })
+            .map(|(challenge_id, _)| *challenge_id)
+            .take(limit as usize)
+            .collect();
+        for challenge_id in &expired {
+            self.challenges.remove(challenge_id);
+        }
+        expired.len() as u32
+    }
+
+    /// 按总得分排名的前 `limit` 名玩家
+    pub fn leaderboard(&self, limit: u32) -> Vec<(ActorId, PlayerStats)> {
+        let mut players: Vec<_> = self
+            .stats
+            .iter()
+            .map(|(user, stats)| (*user, stats.clone()))
+            .collect();
+        players
+            .sort_by(|(_, a), (_, b)| b.total_score.cmp(&a.total_score).then(b.wins.cmp(&a.wins)));
+        players.truncate(limit as usize);
+        players
+    }
 }
 
 impl From<&GameSession> for GameSessionState {
     fn from(game_session: &GameSession) -> Self {
         Self {
             wordle_program_id: game_session.wordle_program_id,
-            game_sessions: game_session
-                .sessions
+            ft_program_id: game_session.ft_program_id,
+            admin: game_session.admin,
+            games: game_session.games.iter().map(|(k, v)| (*k, *v)).collect(),
+            config: game_session.config.clone(),
+            reward_config: game_session.reward_config.clone(),
+            pool: game_session.pool,
+            pending_payouts: game_session
+                .pending_payouts
+                .iter()
+                .map(|(k, v)| (*k, *v))
+                .collect(),
+            pending_rewards: game_session
+                .pending_rewards
+                .iter()
+                .map(|(k, v)| (*k, v.clone()))
+                .collect(),
+            session_count: game_session.sessions.len() as u32,
+            anomalies: game_session.anomalies.clone(),
+            races: game_session
+                .races
                 .iter()
                 .map(|(k, v)| (*k, v.clone()))
                 .collect(),
+            challenges: game_session
+                .challenges
+                .iter()
+                .map(|(id, challenge)| (*id, challenge.challenger, challenge.opponent))
+                .collect(),
+            reservations: game_session.reservations.clone(),
+            paused: game_session.paused,
+            migrated: game_session.migrated,
+            importing: game_session.importing.is_some(),
+            unscheduled_checks: game_session.unscheduled_checks.len() as u32,
         }
     }
 }
@@ -169,6 +1682,616 @@ impl Metadata for GameSessionMetadata {
     type Reply = ();
     type Others = ();
     type Signal = ();
-    type State = Out<GameSessionState>;
+    type State = InOut<StateQuery, StateReply>;
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum StateQuery {
+    Full,
+    /// 玩家已归档的会话，从第 `offset` 条开始最多返回 `limit` 条
+    Archive {
+        user: ActorId,
+        offset: u32,
+        limit: u32,
+    },
+    PlayerStats(ActorId),
+    /// 按总得分排名的前 `limit` 名玩家
+    Leaderboard {
+        limit: u32,
+    },
+    /// 保存的会话，按 (玩家, session_id) 排序，从第 `offset` 个开始最多返回 `limit` 个。
+    /// `user` 不为空时只返回该玩家的
+    Sessions {
+        user: Option<ActorId>,
+        offset: u32,
+        limit: u32,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum StateReply {
+    Full(GameSessionState),
+    Archive {
+        total: u32,
+        sessions: Vec<ArchivedSession>,
+    },
+    PlayerStats(Option<PlayerStats>),
+    Leaderboard(Vec<(ActorId, PlayerStats)>),
+    Sessions {
+        total: u32,
+        sessions: Vec<(ActorId, SessionInfo)>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transition_table() {
+        let user = ActorId::zero();
+        let started = SessionStatus::ReplyReceived(GameReply::Started);
+        let checked = SessionStatus::ReplyReceived(GameReply::Moved(MoveResult::Word {
+            correct_positions: vec![0],
+            contained_in_word: vec![1],
+        }));
+        let start = GameSessionAction::StartGame;
+        let start_with = GameSessionAction::StartGameWith { program_id: user };
+        let play = GameSessionAction::Play {
+            session_id: MessageId::zero(),
+            input: GameInput::Pebbles(1),
+        };
+        let abandon = GameSessionAction::Abandon {
+            session_id: MessageId::zero(),
+        };
+        let restart = GameSessionAction::Restart {
+            session_id: MessageId::zero(),
+        };
+        let check = GameSessionAction::CheckWord {
+            session_id: MessageId::zero(),
+            word: "house".to_string(),
+        };
+        let timeout = GameSessionAction::CheckGameStatus {
+            user,
+            session_id: MessageId::zero(),
+        };
+        let update_config = GameSessionAction::UpdateConfig(GameSessionConfig::default());
+        let race_game = GameSessionAction::StartRaceGame { race_id: 0 };
+        let join_race = GameSessionAction::JoinRace { race_id: 0 };
+        let accept = GameSessionAction::AcceptChallenge {
+            challenge_id: MessageId::zero(),
+        };
+        let challenge = GameSessionAction::Challenge {
+            opponent: user,
+            word: "house".to_string(),
+        };
+        let in_game = Transition::Reject(GameSessionError::AlreadyInGame);
+        let not_in_game = Transition::Reject(GameSessionError::NotInGame);
+        let pending = Transition::Reject(GameSessionError::ReplyPending);
+
+        // (状态, 动作, 是否为等待中的消息, 期望的转换)
+        let table = [
+            (SessionStatus::Init, &start, false, Transition::StartGame),
+            (SessionStatus::Init, &check, false, not_in_game),
+            (SessionStatus::Init, &timeout, false, Transition::Ignore),
+            (SessionStatus::WaitUserInput, &start, false, in_game),
+            (
+                SessionStatus::WaitUserInput,
+                &check,
+                false,
+                Transition::SendGuess,
+            ),
+            (
+                SessionStatus::WaitUserInput,
+                &timeout,
+                false,
+                Transition::Timeout,
+            ),
+            (SessionStatus::WaitWordleStartReply, &start, false, pending),
+            (
+                SessionStatus::WaitWordleStartReply,
+                &start,
+                true,
+                Transition::WordleUnavailable,
+            ),
+            (SessionStatus::WaitWordleStartReply, &check, false, pending),
+            (
+                SessionStatus::WaitWordleStartReply,
+                &timeout,
+                false,
+                Transition::Timeout,
+            ),
+            (
+                SessionStatus::WaitWordleCheckWordReply,
+                &start,
+                false,
+                in_game,
+            ),
+            (
+                SessionStatus::WaitWordleCheckWordReply,
+                &check,
+                false,
+                pending,
+            ),
+            (
+                SessionStatus::WaitWordleCheckWordReply,
+                &check,
+                true,
+                Transition::WordleUnavailable,
+            ),
+            (
+                SessionStatus::WaitWordleCheckWordReply,
+                &timeout,
+                false,
+                Transition::Timeout,
+            ),
+            (started.clone(), &start, true, Transition::GameStarted),
+            (started.clone(), &start, false, pending),
+            (started.clone(), &check, true, pending),
+            (started.clone(), &check, false, pending),
+            (started, &timeout, false, Transition::Timeout),
+            (checked.clone(), &start, true, pending),
+            (checked.clone(), &start, false, pending),
+            (checked.clone(), &check, true, Transition::GuessChecked),
+            (checked.clone(), &check, false, pending),
+            (checked, &timeout, false, Transition::Timeout),
+            (
+                SessionStatus::GameOver(GameStatus::Win),
+                &start,
+                false,
+                Transition::StartGame,
+            ),
+            (
+                SessionStatus::GameOver(GameStatus::Lose),
+                &start,
+                true,
+                not_in_game,
+            ),
+            (
+                SessionStatus::GameOver(GameStatus::Lose),
+                &check,
+                false,
+                not_in_game,
+            ),
+            (
+                SessionStatus::GameOver(GameStatus::Lose),
+                &timeout,
+                false,
+                Transition::Ignore,
+            ),
+            (
+                SessionStatus::Init,
+                &start_with,
+                false,
+                Transition::StartGame,
+            ),
+            (SessionStatus::Init, &play, false, not_in_game),
+            (
+                SessionStatus::WaitUserInput,
+                &play,
+                false,
+                Transition::SendGuess,
+            ),
+            (
+                SessionStatus::WaitWordleCheckWordReply,
+                &play,
+                true,
+                Transition::WordleUnavailable,
+            ),
+            (started.clone(), &start_with, true, Transition::GameStarted),
+            (checked.clone(), &play, true, Transition::GuessChecked),
+            (SessionStatus::Init, &abandon, false, not_in_game),
+            (
+                SessionStatus::WaitUserInput,
+                &abandon,
+                false,
+                Transition::Abandon,
+            ),
+            (
+                SessionStatus::WaitUserInput,
+                &restart,
+                false,
+                Transition::Abandon,
+            ),
+            (
+                SessionStatus::WaitWordleCheckWordReply,
+                &abandon,
+                false,
+                pending,
+            ),
+            (checked.clone(), &restart, true, pending),
+            (
+                SessionStatus::GameOver(GameStatus::Lose),
+                &restart,
+                false,
+                not_in_game,
+            ),
+            (
+                SessionStatus::Init,
+                &update_config,
+                false,
+                Transition::Ignore,
+            ),
+            (
+                SessionStatus::WaitUserInput,
+                &update_config,
+                false,
+                Transition::Ignore,
+            ),
+            (
+                SessionStatus::Init,
+                &race_game,
+                false,
+                Transition::StartGame,
+            ),
+            (SessionStatus::WaitUserInput, &race_game, false, in_game),
+            (SessionStatus::Init, &join_race, false, Transition::Ignore),
+            (SessionStatus::Init, &accept, false, Transition::StartGame),
+            (
+                SessionStatus::WaitWordleStartReply,
+                &accept,
+                true,
+                Transition::WordleUnavailable,
+            ),
+            (
+                SessionStatus::WaitUserInput,
+                &challenge,
+                false,
+                Transition::Ignore,
+            ),
+        ];
+
+        for (status, action, is_waiting_msg, expected) in table {
+            assert_eq!(
+                status.transition(action, is_waiting_msg),
+                expected,
+                "{status:?} × {action:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn expects_reply() {
+        let started = GameReply::Started;
+        let won = GameReply::Won(MoveResult::Pebbles {
+            pebbles_remaining: 0,
+        });
+
+        assert!(SessionStatus::WaitWordleStartReply.expects_reply(&started));
+        assert!(!SessionStatus::WaitWordleStartReply.expects_reply(&won));
+        assert!(SessionStatus::WaitWordleCheckWordReply.expects_reply(&won));
+        assert!(!SessionStatus::WaitWordleCheckWordReply.expects_reply(&started));
+        assert!(!SessionStatus::WaitUserInput.expects_reply(&won));
+    }
+
+    #[test]
+    fn decode_reply() {
+        let user = ActorId::zero();
+        let wordle_won = WordleEvent::GameWon { user }.encode();
+        assert!(matches!(
+            GameKind::Wordle.decode_reply(&wordle_won, false),
+            Some(GameReply::Won(MoveResult::Word { .. }))
+        ));
+
+        let pebbles = GameKind::Pebbles(PebblesInit {
+            difficulty: DifficultyLevel::Easy,
+            pebbles_count: 5,
+            max_pebbles_per_turn: 2,
+        });
+        let restarted = PebblesInit::default().encode();
+        assert!(matches!(
+            pebbles.decode_reply(&restarted, true),
+            Some(GameReply::Started)
+        ));
+        let counter_turn = PebblesEvent::CounterTurn(3).encode();
+        assert!(matches!(
+            pebbles.decode_reply(&counter_turn, false),
+            Some(GameReply::Moved(MoveResult::Pebbles {
+                pebbles_remaining: 3
+            }))
+        ));
+        let lost = PebblesEvent::Won(PebblesPlayer::Program).encode();
+        assert!(matches!(
+            pebbles.decode_reply(&lost, false),
+            Some(GameReply::Lost(_, None))
+        ));
+        assert!(pebbles.decode_reply(&[0xff], false).is_none());
 
+        assert!(pebbles
+            .move_message(user, &GameInput::Word("house".to_string()))
+            .is_none());
+
+        // 答案的回复不是对游戏操作的回复
+        let revealed = WordleEvent::Revealed {
+            user,
+            answer: "horse".to_string(),
+        }
+        .encode();
+        assert!(GameKind::Wordle.decode_reply(&revealed, false).is_none());
+        assert_eq!(
+            GameKind::Wordle.decode_answer(&revealed).as_deref(),
+            Some("horse")
+        );
+        assert!(pebbles.decode_answer(&revealed).is_none());
+    }
+
+    #[test]
+    fn wordle_codec() {
+        use wordle_io::{Action, Event};
+
+        // 与 wordle-io 中的同名变体编码相同
+        let user = ActorId::from(1);
+        let owner = ActorId::from(2);
+        let word = || "house".to_string();
+        let actions = [
+            (WordleAction::StartGame { user }, Action::StartGame { user }),
+            (
+                WordleAction::CheckWord { user, word: word() },
+                Action::CheckWord { user, word: word() },
+            ),
+            (WordleAction::Reveal { user }, Action::Reveal { user }),
+            (
+                WordleAction::StartSharedGame { user, shared_id: 7 },
+                Action::StartSharedGame { user, shared_id: 7 },
+            ),
+            (
+                WordleAction::StartGameWithWord { user, word: word() },
+                Action::StartGameWithWord { user, word: word() },
+            ),
+            (
+                WordleAction::Forget {
+                    users: vec![user],
+                    shared_ids: vec![7],
+                },
+                Action::Forget {
+                    users: vec![user],
+                    shared_ids: vec![7],
+                },
+            ),
+            (
+                WordleAction::StartSharedGameOf {
+                    user,
+                    owner,
+                    shared_id: 7,
+                },
+                Action::StartSharedGameOf {
+                    user,
+                    owner,
+                    shared_id: 7,
+                },
+            ),
+            (WordleAction::EndGame { user }, Action::EndGame { user }),
+        ];
+        for (action, wordle_action) in actions {
+            assert_eq!(action.encode(), wordle_action.encode());
+        }
+
+        let events = [
+            (
+                WordleEvent::GameStarted { user },
+                Event::GameStarted { user },
+            ),
+            (
+                WordleEvent::WordChecked {
+                    user,
+                    correct_positions: vec![0],
+                    contained_in_word: vec![1],
+                },
+                Event::WordChecked {
+                    user,
+                    correct_positions: vec![0],
+                    contained_in_word: vec![1],
+                },
+            ),
+            (WordleEvent::GameWon { user }, Event::GameWon { user }),
+            (
+                WordleEvent::GameLost {
+                    user,
+                    correct_positions: vec![0],
+                    contained_in_word: vec![1],
+                    answer: word(),
+                },
+                Event::GameLost {
+                    user,
+                    correct_positions: vec![0],
+                    contained_in_word: vec![1],
+                    answer: word(),
+                },
+            ),
+            (
+                WordleEvent::Revealed {
+                    user,
+                    answer: word(),
+                },
+                Event::Revealed {
+                    user,
+                    answer: word(),
+                },
+            ),
+        ];
+        for (event, wordle_event) in events {
+            assert_eq!(event.encode(), wordle_event.encode());
+        }
+    }
+
+    #[test]
+    fn board_row() {
+        let guess = GuessRecord {
+            word: "house".to_string(),
+            correct_positions: vec![0, 4],
+            contained_in_word: vec![2],
+        };
+        assert_eq!(
+            BoardRow::from(&guess).letters,
+            vec![
+                LetterState::Correct,
+                LetterState::Absent,
+                LetterState::Present,
+                LetterState::Absent,
+                LetterState::Correct,
+            ]
+        );
+    }
+
+    #[test]
+    fn keyboard() {
+        let mut keyboard = Keyboard::default();
+        keyboard.record(&BoardRow::from(&GuessRecord {
+            word: "house".to_string(),
+            correct_positions: vec![0],
+            contained_in_word: vec![2],
+        }));
+        // 同一个字母只会升级，不会降级
+        keyboard.record(&BoardRow::from(&GuessRecord {
+            word: "shore".to_string(),
+            correct_positions: vec![1, 4],
+            contained_in_word: vec![],
+        }));
+        assert_eq!(keyboard.get('h'), Some(LetterState::Correct));
+        assert_eq!(keyboard.get('u'), Some(LetterState::Present));
+        assert_eq!(keyboard.get('s'), Some(LetterState::Absent));
+        assert_eq!(keyboard.get('e'), Some(LetterState::Correct));
+        assert_eq!(keyboard.get('z'), None);
+        assert_eq!(keyboard.get('A'), None);
+    }
+
+    #[test]
+    fn win_score() {
+        assert_eq!(super::win_score(1, 5, 0), 600);
+        assert_eq!(super::win_score(5, 5, 0), 200);
+        assert_eq!(super::win_score(2, 5, 30), 470);
+        // 上限为 8 时第 8 次猜中与默认上限下第 5 次猜中得分相同
+        assert_eq!(super::win_score(1, 8, 0), 900);
+        assert_eq!(super::win_score(8, 8, 0), 200);
+        assert_eq!(super::win_score(3, 8, 150), 600);
+        assert_eq!(super::win_score(1, 1, 0), 200);
+        // 不限制尝试次数时只按区块计分
+        assert_eq!(super::win_score(7, 0, 40), 60);
+    }
+
+    #[test]
+    fn prune_sessions() {
+        let mut game_session = GameSession {
+            config: GameSessionConfig {
+                retention_blocks: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let user = ActorId::from(1);
+        let other_user = ActorId::from(2);
+        let ended = |end_block| SessionInfo {
+            session_status: SessionStatus::GameOver(GameStatus::Lose),
+            end_block,
+            ..Default::default()
+        };
+        let keys = [
+            (user, MessageId::from([1; 32])),
+            (user, MessageId::from([2; 32])),
+            (user, MessageId::from([3; 32])),
+            (other_user, MessageId::from([4; 32])),
+            (user, MessageId::from([5; 32])),
+        ];
+        game_session.sessions.insert(keys[0], ended(5));
+        game_session.sessions.insert(keys[1], ended(15));
+        game_session
+            .sessions
+            .insert(keys[2], SessionInfo::default());
+        game_session.sessions.insert(keys[3], ended(5));
+        game_session.sessions.insert(keys[4], ended(0));
+        // 仍在等待答案
+        game_session
+            .reveal_messages
+            .insert(MessageId::from([6; 32]), keys[4]);
+
+        assert_eq!(
+            game_session.prune_sessions(20, Some(user), u32::MAX).len(),
+            1
+        );
+        assert!(!game_session.sessions.contains_key(&keys[0]));
+        assert_eq!(game_session.prune_sessions(20, None, u32::MAX).len(), 1);
+        assert!(!game_session.sessions.contains_key(&keys[3]));
+        assert_eq!(game_session.prune_sessions(20, None, u32::MAX).len(), 0);
+
+        game_session.reveal_messages.clear();
+        assert_eq!(game_session.prune_sessions(25, None, 1).len(), 1);
+        assert_eq!(game_session.prune_sessions(25, None, 1).len(), 1);
+        // 尚未结束的会话不会被清理
+        assert_eq!(game_session.sessions.len(), 1);
+        assert!(game_session.sessions.contains_key(&keys[2]));
+    }
+
+    #[test]
+    fn prune_races_and_challenges() {
+        let mut game_session = GameSession {
+            config: GameSessionConfig {
+                retention_blocks: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let user = ActorId::from(1);
+        let active_key = (user, MessageId::from([1; 32]));
+        let ended_key = (user, MessageId::from([2; 32]));
+        game_session.sessions.insert(
+            active_key,
+            SessionInfo {
+                session_status: SessionStatus::WaitUserInput,
+                ..Default::default()
+            },
+        );
+        game_session.sessions.insert(
+            ended_key,
+            SessionInfo {
+                session_status: SessionStatus::GameOver(GameStatus::Lose),
+                ..Default::default()
+            },
+        );
+        let race = |last_block, sessions| Race {
+            host: user,
+            players: vec![user],
+            sessions,
+            status: RaceStatus::Running,
+            last_block,
+        };
+        game_session.races.insert(0, race(0, vec![ended_key]));
+        game_session.races.insert(1, race(15, vec![]));
+        // 还有进行中的会话
+        game_session.races.insert(2, race(0, vec![active_key]));
+
+        let pruned = game_session.prune_races(20, u32::MAX);
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].0, 0);
+        assert_eq!(game_session.prune_races(25, u32::MAX).len(), 1);
+        assert!(game_session.races.contains_key(&2));
+
+        let challenge = |created_block, session_id| Challenge {
+            challenger: user,
+            opponent: ActorId::from(2),
+            word: "house".to_string(),
+            session_id,
+            created_block,
+        };
+        let challenge_ids = [
+            MessageId::from([3; 32]),
+            MessageId::from([4; 32]),
+            MessageId::from([5; 32]),
+        ];
+        game_session
+            .challenges
+            .insert(challenge_ids[0], challenge(0, None));
+        game_session
+            .challenges
+            .insert(challenge_ids[1], challenge(15, None));
+        // 已被接受
+        game_session
+            .challenges
+            .insert(challenge_ids[2], challenge(0, Some(active_key.1)));
+
+        assert_eq!(game_session.prune_challenges(20, u32::MAX), 1);
+        assert!(!game_session.challenges.contains_key(&challenge_ids[0]));
+        assert_eq!(game_session.prune_challenges(25, 0), 0);
+        assert_eq!(game_session.prune_challenges(25, u32::MAX), 1);
+        assert!(game_session.challenges.contains_key(&challenge_ids[2]));
+    }
 }
@@ -0,0 +1,138 @@
+//! 基于 gstd async 消息的请求/回复流程：发送请求后 `.await` 后端游戏的回复，
+//! 最多等待 `reply_timeout_blocks` 个区块。回复由 gstd 按消息ID 交给等待中的
+//! 消息，`handle_reply` 只处理奖励、答案和不再等待的回复。
+
+use super::*;
+use gstd::errors::Error;
+
+#[gstd::async_main(handle_reply = on_reply)]
+async fn main() {
+    // 解码并处理游戏会话动作
+    let game_session_action: GameSessionAction = msg::load().expect("无法解码 GameSessionAction");
+
+    // 等待回复期间其他消息会修改状态，因此不跨越 `.await` 持有状态的引用
+    let result = match game_session_action {
+        GameSessionAction::StartGame
+        | GameSessionAction::CheckWord { .. }
+        | GameSessionAction::StartGameWith { .. }
+        | GameSessionAction::Play { .. }
+        | GameSessionAction::StartRaceGame { .. }
+        | GameSessionAction::AcceptChallenge { .. } => {
+            handle_session_action(game_session_action).await
+        }
+        // 放弃和开始在同一条消息中完成
+        GameSessionAction::Restart { session_id } => {
+            match restart_session(get_game_session_mut(), session_id) {
+                Ok(program_id) => {
+                    handle_session_action(GameSessionAction::StartGameWith { program_id }).await
+                }
+                Err(error) => Err(error),
+            }
+        }
+        game_session_action => process_action(get_game_session_mut(), game_session_action),
+    };
+    reply_result(result);
+}
+
+async fn handle_session_action(
+    game_session_action: GameSessionAction,
+) -> Result<(), GameSessionError> {
+    let game_session = get_game_session_mut();
+    let key = open_session(game_session, &game_session_action)?;
+    let reply_timeout_blocks = game_session.config.reply_timeout_blocks;
+    let session_info = &game_session.sessions[&key];
+    // 新的消息不会是正在等待回复的那条消息
+    let transition = session_info
+        .session_status
+        .transition(&game_session_action, false);
+
+    let (game_program_id, payload, input) = match transition {
+        Transition::StartGame => (
+            session_info.game_program_id,
+            game_session.start_message(key, wordle_player(key.1)),
+            None,
+        ),
+        Transition::SendGuess => {
+            let (input, payload) =
+                move_request(session_info, wordle_player(key.1), game_session_action)?;
+            (session_info.game_program_id, payload, Some(input))
+        }
+        Transition::Reject(error) => return Err(error),
+        _ => unreachable!("新的会话动作只会开始游戏、发送操作或被拒绝"),
+    };
+    let future = msg::send_bytes_for_reply(game_program_id, payload, 0, 0)
+        .and_then(|future| future.up_to(Some(reply_timeout_blocks)))
+        .expect("发送消息失败");
+    let request_id = future.waiting_reply_to;
+    match input {
+        None => begin_session(game_session, key, request_id),
+        Some(input) => move_sent(game_session, key, request_id, input),
+    }
+
+    let response = future.await;
+
+    let game_session = get_game_session_mut();
+    // 等待期间会话可能已经因超时结束，入场费已计入奖池，不再退还
+    let Some(session_info) = game_session
+        .sessions
+        .get(&key)
+        .filter(|s| s.send_to_wordle_msg_id == request_id && s.is_wait_reply_status())
+    else {
+        msg::reply(GameSessionEvent::Error(GameSessionError::NotInGame), 0).expect("回复消息失败");
+        return Ok(());
+    };
+    game_session.game_messages.remove(&request_id);
+
+    // 由会话的后端游戏解码回复，只接受类型相符的回复
+    let is_start = matches!(
+        session_info.session_status,
+        SessionStatus::WaitWordleStartReply
+    );
+    let reply = match response {
+        Ok(payload) => match session_info.game.decode_reply(&payload, is_start) {
+            Some(reply) if session_info.session_status.expects_reply(&reply) => Ok(reply),
+            Some(_) => Err(Some(AnomalyKind::UnexpectedReply)),
+            None => Err(Some(AnomalyKind::Undecodable)),
+        },
+        Err(Error::Timeout(..)) => Err(None),
+        Err(_) => Err(Some(AnomalyKind::ErrorReply)),
+    };
+    match reply {
+        Ok(GameReply::Started) => game_started(game_session, key),
+        Ok(reply) => move_checked(game_session, key, reply),
+        Err(kind) => {
+            if let Some(kind) = kind {
+                game_session.record_anomaly(anomaly(request_id, game_program_id, kind));
+            }
+            backend_unavailable(game_session, key);
+        }
+    }
+    Ok(())
+}
+
+// 在 gstd 把回复交给等待中的消息之后执行
+fn on_reply() {
+    let reply_to = msg::reply_to().expect("查询 reply_to 数据失败");
+    let source = msg::source();
+    if source == exec::program_id() {
+        // 延迟的 CheckGameStatus 消息的自动回复
+        return;
+    }
+    let game_session = get_game_session_mut();
+    if handle_side_reply(game_session, reply_to, source) {
+        return;
+    }
+    // 仍在等待的请求由等待中的消息处理，已经超时或会话已经结束的请求记为异常
+    let kind = match game_session
+        .game_messages
+        .get(&reply_to)
+        .and_then(|key| game_session.sessions.get(key))
+    {
+        None => AnomalyKind::UnknownMessage,
+        Some(session_info) if source != session_info.game_program_id => {
+            AnomalyKind::UnexpectedSource
+        }
+        Some(_) => return,
+    };
+    game_session.record_anomaly(anomaly(reply_to, source, kind));
+}
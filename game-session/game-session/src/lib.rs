@@ -1,169 +1,1193 @@
 #![no_std]
 use game_session_io::*;
-use gstd::{exec, msg};
+use gstd::{collections::HashMap, exec, msg, prelude::*, ActorId, MessageId, ReservationId};
 
-// 尝试次数的上限
-const TRIES_LIMIT: u8 = 5;
+// 与后端游戏之间的请求和回复有两种实现，由 `async` 特性选择，对外的接口和行为相同
+#[cfg(feature = "async")]
+mod async_flow;
+#[cfg(not(feature = "async"))]
+mod state_machine;
 
 // 全局静态变量，用于存储游戏会话状态
 static mut GAME_SESSION_STATE: Option<GameSession> = None;
 
+// 创建竞速或挑战时顺便清理的过期竞速或挑战的最大数量
+const LAZY_PRUNE_LIMIT: u32 = 10;
+
 #[no_mangle]
 extern "C" fn init() {
     // 接收并解码游戏会话初始化数据
-    let game_session_init: GameSessionInit =
-        msg::load().expect("无法解码 GameSessionInit");
-    game_session_init.assert_valid();  // 验证数据有效性
+    let mut game_session_init: GameSessionInit = msg::load().expect("无法解码 GameSessionInit");
+    game_session_init.assert_valid(); // 验证数据有效性
+    let import = game_session_init.import.take();
+    let mut game_session: GameSession = game_session_init.into();
+    game_session.admin = msg::source(); // 部署者为管理员
+    if let Some(snapshot) = import {
+        // 从旧版本程序迁移，其余各页导入后由管理员恢复运行
+        game_session.import(snapshot);
+        game_session.paused = true;
+        game_session.pool = msg::value();
+    }
     unsafe {
         // 初始化游戏会话状态
-        GAME_SESSION_STATE = Some(game_session_init.into());
+        GAME_SESSION_STATE = Some(game_session);
     };
 }
 
-#[no_mangle]
-extern "C" fn handle() {
-    // 解码并处理游戏会话动作
-    let game_session_action: GameSessionAction =
-        msg::load().expect("无法解码 GameSessionAction");
-    let game_session = get_game_session_mut();
-    match game_session_action {
-        GameSessionAction::StartGame => {
-            let user = msg::source();  // 获取消息发送者，即玩家
-            let session_info = game_session.sessions.entry(user).or_default();
-            match &session_info.session_status {
-                SessionStatus::ReplyReceived(wordle_event) => {
-                    // 如果之前收到过回复，则回复玩家游戏已启动
-                    msg::reply::<GameSessionEvent>(wordle_event.into(), 0)
-                        .expect("回复消息失败");
-                    session_info.session_status = SessionStatus::WaitUserInput; // 更新状态为等待玩家输入
-                }
-                SessionStatus::Init
-                | SessionStatus::GameOver(..)
-                | SessionStatus::WaitWordleStartReply => {
-                    // 向Wordle程序发送"StartGame"消息
-                    let send_to_wordle_msg_id = msg::send(
-                        game_session.wordle_program_id,
-                        WordleAction::StartGame { user },
-                        0,
-                    )
-                    .expect("发送消息失败");
+// 出错时状态保持不变，以错误回复玩家并退还附带的金额
+fn reply_result(result: Result<(), GameSessionError>) {
+    if let Err(error) = result {
+        msg::reply(GameSessionEvent::Error(error), msg::value()).expect("回复消息失败");
+    }
+}
 
-                    session_info.session_id = msg::id();  // 保存当前消息ID
-                    session_info.original_msg_id = msg::id();  // 保存初始消息ID
-                    session_info.send_to_wordle_msg_id = send_to_wordle_msg_id;  // 保存发送到Wordle的消息ID
-                    session_info.tries = 0;  // 初始化尝试次数
-                    session_info.session_status = SessionStatus::WaitWordleStartReply;  // 更新状态为等待Wordle启动回复
-                    msg::send_delayed(
-                        exec::program_id(),
-                        GameSessionAction::CheckGameStatus {
-                            user,
-                            session_id: msg::id(),
-                        },
-                        1000000,
-                        200,
-                    )
-                    .expect("发送延迟消息失败");
-                    exec::wait();  // 等待回复
-                }
-                SessionStatus::WaitUserInput | SessionStatus::WaitWordleCheckWordReply => {
-                    panic!("用户已经在游戏中");
-                }
-            }
-        }
-        GameSessionAction::CheckWord { word } => {
+// 处理不需要等待后端游戏回复的动作
+fn process_action(
+    game_session: &mut GameSession,
+    game_session_action: GameSessionAction,
+) -> Result<(), GameSessionError> {
+    // 导出状态后只允许管理员取回资金和 gas 预留
+    if !matches!(
+        game_session_action,
+        GameSessionAction::CheckGameStatus { .. }
+            | GameSessionAction::Withdraw { .. }
+            | GameSessionAction::UnreserveGas { .. }
+            | GameSessionAction::ExportState { .. }
+    ) {
+        check_not_migrated(game_session)?;
+    }
+    // 导入状态期间只允许管理员继续导入和恢复运行
+    if !matches!(
+        game_session_action,
+        GameSessionAction::ImportState(..) | GameSessionAction::Resume
+    ) {
+        check_not_importing(game_session)?;
+    }
+    match game_session_action {
+        GameSessionAction::StartGame
+        | GameSessionAction::CheckWord { .. }
+        | GameSessionAction::StartGameWith { .. }
+        | GameSessionAction::Play { .. }
+        | GameSessionAction::StartRaceGame { .. }
+        | GameSessionAction::AcceptChallenge { .. }
+        | GameSessionAction::Restart { .. } => {
+            unreachable!("会话动作由 handle 单独处理");
+        }
+        GameSessionAction::Abandon { session_id } => {
+            let game_over = abandon_session(game_session, msg::source(), session_id)?;
+            msg::reply(game_over, 0).expect("回复消息失败");
+        }
+        GameSessionAction::CheckGameStatus { user, session_id } => {
+            // 延迟消息只接受来自程序自身的，导出状态后由新程序检查
+            if msg::source() == exec::program_id() && !game_session.migrated {
+                check_game_status(game_session, user, session_id);
+            }
+        }
+        GameSessionAction::UpdateConfig(config) => {
+            check_admin(game_session)?;
+            if !config.is_valid() {
+                return Err(GameSessionError::InvalidConfig);
+            }
+            game_session.config = config;
+            msg::reply(GameSessionEvent::ConfigUpdated, 0).expect("回复消息失败");
+        }
+        GameSessionAction::Prune { limit } => {
+            check_admin(game_session)?;
+            let now = exec::block_height();
+            let sessions = game_session.prune_sessions(now, None, limit);
+            let limit = limit - sessions.len() as u32;
+            let races = game_session.prune_races(now, limit);
+            let limit = limit - races.len() as u32;
+            let challenges = game_session.prune_challenges(now, limit);
+            forget_in_wordle(game_session, &sessions, &races);
+            msg::reply(
+                GameSessionEvent::Pruned {
+                    sessions: sessions.len() as u32,
+                    races: races.len() as u32,
+                    challenges,
+                },
+                0,
+            )
+            .expect("回复消息失败");
+        }
+        GameSessionAction::ReserveGas { count } => {
+            check_admin(game_session)?;
+            let amount = game_session.config.check_status_gas;
+            let duration = game_session.config.reservation_blocks;
+            let now = exec::block_height();
+            game_session
+                .reservations
+                .retain(|reservation| reservation.expires_at > now);
+            for _ in 0..count {
+                // gas 不足时停止，已经预留的保留
+                let Ok(id) = ReservationId::reserve(amount, duration) else {
+                    break;
+                };
+                game_session.reservations.push(GasReservation {
+                    id,
+                    amount,
+                    expires_at: now.saturating_add(duration),
+                });
+            }
+            msg::reply(
+                GameSessionEvent::GasReserved {
+                    available: game_session.reservations.len() as u32,
+                },
+                0,
+            )
+            .expect("回复消息失败");
+        }
+        GameSessionAction::UnreserveGas { count } => {
+            check_admin(game_session)?;
+            for _ in 0..count {
+                let Some(reservation) = game_session.reservations.pop() else {
+                    break;
+                };
+                // 已到期的预留无法取消，直接丢弃
+                let _ = reservation.id.unreserve();
+            }
+            msg::reply(
+                GameSessionEvent::GasUnreserved {
+                    available: game_session.reservations.len() as u32,
+                },
+                0,
+            )
+            .expect("回复消息失败");
+        }
+        GameSessionAction::Pause => {
+            check_admin(game_session)?;
+            game_session.paused = true;
+            msg::reply(GameSessionEvent::Paused, 0).expect("回复消息失败");
+        }
+        GameSessionAction::Resume => {
+            check_admin(game_session)?;
+            game_session.finish_import();
+            game_session.paused = false;
+            msg::reply(GameSessionEvent::Resumed, 0).expect("回复消息失败");
+        }
+        GameSessionAction::ExportState { offset, limit } => {
+            check_admin(game_session)?;
+            // 暂停后状态只会因等待中的回复和超时检查而改变
+            if !game_session.paused {
+                return Err(GameSessionError::NotPaused);
+            }
+            // 等待中的回复会在导出后改变旧程序的状态
+            if !game_session.game_messages.is_empty()
+                || !game_session.reveal_messages.is_empty()
+                || !game_session.pending_rewards.is_empty()
+            {
+                return Err(GameSessionError::ReplyPending);
+            }
+            // 之后玩家只能在新程序中继续，两边的会话不会分叉
+            game_session.migrated = true;
+            let (total, snapshot) = game_session.snapshot(exec::program_id(), offset, limit);
+            msg::reply(GameSessionEvent::StateExported { total, snapshot }, 0)
+                .expect("回复消息失败");
+        }
+        GameSessionAction::ImportState(snapshot) => {
+            check_admin(game_session)?;
+            let program_id = game_session
+                .importing
+                .ok_or(GameSessionError::NotImporting)?;
+            if snapshot.version != STATE_VERSION || snapshot.program_id != program_id {
+                return Err(GameSessionError::InvalidSnapshot);
+            }
+            game_session.import(snapshot);
+            msg::reply(GameSessionEvent::StateImported, 0).expect("回复消息失败");
+        }
+        GameSessionAction::ScheduleChecks { limit } => {
+            check_admin(game_session)?;
+            let keys: Vec<_> = game_session
+                .unscheduled_checks
+                .iter()
+                .take(limit as usize)
+                .copied()
+                .collect();
+            for key in keys {
+                schedule_imported_check(game_session, key);
+            }
+            msg::reply(
+                GameSessionEvent::ChecksScheduled {
+                    remaining: game_session.unscheduled_checks.len() as u32,
+                },
+                0,
+            )
+            .expect("回复消息失败");
+        }
+        GameSessionAction::Fund => {
+            check_admin(game_session)?;
+            game_session.pool += msg::value();
+            msg::reply(
+                GameSessionEvent::Funded {
+                    pool: game_session.pool,
+                },
+                0,
+            )
+            .expect("回复消息失败");
+        }
+        GameSessionAction::UpdateRewardConfig(reward_config) => {
+            check_admin(game_session)?;
+            if !reward_config.is_valid() {
+                return Err(GameSessionError::InvalidConfig);
+            }
+            game_session.reward_config = reward_config;
+            msg::reply(GameSessionEvent::RewardConfigUpdated, 0).expect("回复消息失败");
+        }
+        GameSessionAction::Withdraw { amount } => {
+            check_admin(game_session)?;
+            if amount > game_session.pool {
+                return Err(GameSessionError::InsufficientPool);
+            }
+            game_session.pool -= amount;
+            msg::reply(GameSessionEvent::Withdrawn { amount }, amount).expect("回复消息失败");
+        }
+        GameSessionAction::RetryPayout { user } => {
+            let amount = game_session
+                .pending_payouts
+                .remove(&user)
+                .unwrap_or_default();
+            pay_reward(game_session, (user, msg::id()), amount);
+            msg::reply(
+                GameSessionEvent::PendingPayout {
+                    user,
+                    amount: game_session
+                        .pending_payouts
+                        .get(&user)
+                        .copied()
+                        .unwrap_or_default(),
+                },
+                0,
+            )
+            .expect("回复消息失败");
+        }
+        GameSessionAction::RegisterGame { program_id, game } => {
+            check_admin(game_session)?;
+            if program_id.is_zero() {
+                return Err(GameSessionError::InvalidProgramId);
+            }
+            game_session.games.insert(program_id, game);
+            msg::reply(GameSessionEvent::GameRegistered { program_id }, 0).expect("回复消息失败");
+        }
+        GameSessionAction::UnregisterGame { program_id } => {
+            check_admin(game_session)?;
+            if program_id == game_session.wordle_program_id {
+                return Err(GameSessionError::CannotUnregisterWordle);
+            }
+            game_session.games.remove(&program_id);
+            msg::reply(GameSessionEvent::GameUnregistered { program_id }, 0).expect("回复消息失败");
+        }
+        GameSessionAction::CreateRace => {
+            let user = msg::source();
+            let now = exec::block_height();
+            let races = game_session.prune_races(now, LAZY_PRUNE_LIMIT);
+            forget_in_wordle(game_session, &[], &races);
+            // 每个玩家同时只能有一个等待玩家加入的竞速
+            if game_session
+                .races
+                .values()
+                .any(|race| race.host == user && race.status == RaceStatus::Open)
+            {
+                return Err(GameSessionError::RaceAlreadyOpen);
+            }
+            let race_id = game_session.next_race_id;
+            game_session.next_race_id += 1;
+            game_session.races.insert(
+                race_id,
+                Race {
+                    host: user,
+                    players: vec![user],
+                    last_block: now,
+                    ..Default::default()
+                },
+            );
+            msg::reply(GameSessionEvent::RaceCreated { race_id }, 0).expect("回复消息失败");
+        }
+        GameSessionAction::JoinRace { race_id } => {
             let user = msg::source();
-            let session_info = game_session.sessions.entry(user).or_default();
-            match &session_info.session_status {
-                SessionStatus::ReplyReceived(wordle_event) => {
-                    session_info.tries += 1;  // 增加尝试次数
-                    if wordle_event.has_guessed() {
-                        // 如果猜对了单词，游戏结束并设置状态为胜利
-                        session_info.session_status = SessionStatus::GameOver(GameStatus::Win);
-                        msg::reply(GameSessionEvent::GameOver(GameStatus::Win), 0)
-                            .expect("回复消息失败");
-                    } else if session_info.tries == TRIES_LIMIT {
-                        // 如果达到尝试次数限制，游戏结束并设置状态为失败
-                        session_info.session_status = SessionStatus::GameOver(GameStatus::Lose);
-                        msg::reply(GameSessionEvent::GameOver(GameStatus::Lose), 0)
-                            .expect("回复消息失败");
-                    } else {
-                        msg::reply::<GameSessionEvent>(wordle_event.into(), 0)
-                            .expect("回复消息失败");
-                        session_info.session_status = SessionStatus::WaitUserInput;  // 更新状态为等待玩家输入
-                    }
-                }
-                SessionStatus::WaitUserInput | SessionStatus::WaitWordleCheckWordReply => {
-                    // 验证提交的单词长度是否为五，并且所有字母为小写
-                    assert!(
-                        word.len() == 5 && word.chars().all(|c| c.is_lowercase()),
-                        "无效的单词"
-                    );
-                    let send_to_wordle_msg_id = msg::send(
-                        game_session.wordle_program_id,
-                        WordleAction::CheckWord { user, word },
-                        0,
-                    )
+            let race = game_session
+                .races
+                .get_mut(&race_id)
+                .ok_or(GameSessionError::RaceNotFound)?;
+            if race.status != RaceStatus::Open {
+                return Err(GameSessionError::RaceNotOpen);
+            }
+            if race.players.contains(&user) {
+                return Err(GameSessionError::AlreadyInRace);
+            }
+            if race.players.len() >= MAX_RACE_PLAYERS {
+                return Err(GameSessionError::RaceFull);
+            }
+            race.players.push(user);
+            msg::reply(
+                GameSessionEvent::RaceJoined {
+                    race_id,
+                    players: race.players.len() as u32,
+                },
+                0,
+            )
+            .expect("回复消息失败");
+        }
+        GameSessionAction::StartRace { race_id } => {
+            let user = msg::source();
+            let race = game_session
+                .races
+                .get_mut(&race_id)
+                .ok_or(GameSessionError::RaceNotFound)?;
+            if race.host != user {
+                return Err(GameSessionError::NotRaceHost);
+            }
+            if race.status != RaceStatus::Open {
+                return Err(GameSessionError::RaceNotOpen);
+            }
+            race.status = RaceStatus::Running;
+            race.last_block = exec::block_height();
+            // 通知其他玩家，创建者收到回复
+            for player in race.players.iter().filter(|player| **player != user) {
+                msg::send(*player, GameSessionEvent::RaceStarted { race_id }, 0)
                     .expect("发送消息失败");
-                    session_info.original_msg_id = msg::id();
-                    session_info.send_to_wordle_msg_id = send_to_wordle_msg_id;
-                    session_info.session_status = SessionStatus::WaitWordleCheckWordReply;  // 更新状态为等待Wordle检查单词回复
-                    exec::wait();  // 等待回复
-                }
-                SessionStatus::Init
-                | SessionStatus::WaitWordleStartReply
-                | SessionStatus::GameOver(..) => {
-                    panic!("用户不在游戏中");
-                }
             }
+            msg::reply(GameSessionEvent::RaceStarted { race_id }, 0).expect("回复消息失败");
         }
-        GameSessionAction::CheckGameStatus { user, session_id } => {
-            if msg::source() == exec::program_id() {
-                if let Some(session_info) = game_session.sessions.get_mut(&user) {
-                    if session_id == session_info.session_id
-                        && !matches!(session_info.session_status, SessionStatus::GameOver(..))
-                    {
-                        session_info.session_status = SessionStatus::GameOver(GameStatus::Lose);  // 如果时间到未完成，游戏结束并设置状态为失败
-                        msg::send(user, GameSessionEvent::GameOver(GameStatus::Lose), 0)
-                            .expect("发送消息失败");
-                    }
-                }
+        GameSessionAction::Challenge { opponent, word } => {
+            let challenger = msg::source();
+            if opponent == challenger || opponent.is_zero() {
+                return Err(GameSessionError::InvalidOpponent);
+            }
+            check_word(&word)?;
+            if !wordle_io::BANK_OF_WORDS.contains(&word.as_str()) {
+                return Err(GameSessionError::WordNotInDictionary);
             }
+            let now = exec::block_height();
+            game_session.prune_challenges(now, LAZY_PRUNE_LIMIT);
+            let pending = game_session
+                .challenges
+                .values()
+                .filter(|challenge| {
+                    challenge.challenger == challenger && challenge.session_id.is_none()
+                })
+                .count();
+            if pending >= MAX_PENDING_CHALLENGES {
+                return Err(GameSessionError::TooManyChallenges);
+            }
+            let challenge_id = msg::id();
+            game_session.challenges.insert(
+                challenge_id,
+                Challenge {
+                    challenger,
+                    opponent,
+                    word,
+                    session_id: None,
+                    created_block: now,
+                },
+            );
+            msg::send(
+                opponent,
+                GameSessionEvent::ChallengeReceived {
+                    challenge_id,
+                    challenger,
+                },
+                0,
+            )
+            .expect("发送消息失败");
+            msg::reply(GameSessionEvent::ChallengeSent { challenge_id }, 0).expect("回复消息失败");
+        }
+        GameSessionAction::DeclineChallenge { challenge_id } => {
+            let challenge = check_challenge(game_session, msg::source(), challenge_id)?;
+            msg::send(
+                challenge.challenger,
+                GameSessionEvent::ChallengeDeclined { challenge_id },
+                0,
+            )
+            .expect("发送消息失败");
+            game_session.challenges.remove(&challenge_id);
+            msg::reply(GameSessionEvent::ChallengeDeclined { challenge_id }, 0)
+                .expect("回复消息失败");
         }
     }
+    Ok(())
 }
 
-#[no_mangle]
-extern "C" fn handle_reply() {
-    let reply_to = msg::reply_to().expect("查询 reply_to 数据失败");
-    let wordle_event: WordleEvent = msg::load().expect("无法解码 WordleEvent");
-    let game_session = get_game_session_mut();
-    let user = wordle_event.get_user();
-    if let Some(session_info) = game_session.sessions.get_mut(user) {
-        if reply_to == session_info.send_to_wordle_msg_id && session_info.is_wait_reply_status() {
-            session_info.session_status = SessionStatus::ReplyReceived(wordle_event);  // 收到Wordle程序的回复
-            exec::wake(session_info.original_msg_id).expect("唤醒消息失败");
+// 验证单词长度是否为五，并且所有字母为小写
+fn check_word(word: &str) -> Result<(), GameSessionError> {
+    if word.len() != 5 || !word.chars().all(|c| c.is_lowercase()) {
+        return Err(GameSessionError::InvalidWord);
+    }
+    Ok(())
+}
+
+// 只有被挑战的玩家可以接受或拒绝，已被接受的挑战不能再次接受
+fn check_challenge(
+    game_session: &GameSession,
+    user: ActorId,
+    challenge_id: MessageId,
+) -> Result<&Challenge, GameSessionError> {
+    game_session
+        .challenges
+        .get(&challenge_id)
+        .filter(|challenge| challenge.opponent == user && challenge.session_id.is_none())
+        .ok_or(GameSessionError::ChallengeNotFound)
+}
+
+// 在竞速中开始会话：竞速必须已经开始，玩家必须在房间中且还没有开始过
+fn check_race_session(
+    game_session: &GameSession,
+    user: ActorId,
+    race_id: u64,
+) -> Result<(), GameSessionError> {
+    let race = game_session
+        .races
+        .get(&race_id)
+        .ok_or(GameSessionError::RaceNotFound)?;
+    if race.status != RaceStatus::Running {
+        return Err(GameSessionError::RaceNotRunning);
+    }
+    if !race.players.contains(&user) {
+        return Err(GameSessionError::NotInRace);
+    }
+    // 没能开始的会话不在 `race.sessions` 中，玩家可以重新开始
+    if race.sessions.iter().any(|(player, _)| *player == user) {
+        return Err(GameSessionError::AlreadyInGame);
+    }
+    Ok(())
+}
+
+// 会话所属的竞速是否已经由其他玩家赢得
+fn race_finished(game_session: &GameSession, key: (ActorId, MessageId)) -> bool {
+    game_session.sessions[&key]
+        .race_id
+        .and_then(|race_id| game_session.races.get(&race_id))
+        .is_some_and(|race| matches!(race.status, RaceStatus::Finished { .. }))
+}
+
+// 会话所属的竞速是否还在进行，这时其他玩家可能还在猜同一个单词
+fn race_running(game_session: &GameSession, key: (ActorId, MessageId)) -> bool {
+    game_session.sessions[&key]
+        .race_id
+        .and_then(|race_id| game_session.races.get(&race_id))
+        .is_some_and(|race| race.status == RaceStatus::Running)
+}
+
+// 竞速的胜者产生：结束其他玩家正在等待输入的会话，向之前已经结束的玩家公开答案，
+// 并通知其他玩家。正在等待后端回复的会话在收到回复时结束
+fn finish_race(game_session: &mut GameSession, race_id: u64, winner: ActorId) {
+    let Some(race) = game_session.races.get_mut(&race_id) else {
+        return;
+    };
+    race.status = RaceStatus::Finished { winner };
+    race.last_block = exec::block_height();
+    let players = race.players.clone();
+    let sessions = race.sessions.clone();
+    for key in sessions {
+        let Some(session_info) = game_session.sessions.get(&key) else {
+            continue;
+        };
+        let is_waiting_input = matches!(session_info.session_status, SessionStatus::WaitUserInput);
+        let is_over = matches!(session_info.session_status, SessionStatus::GameOver(..));
+        if is_waiting_input {
+            let game_over = end_session(game_session, key, GameStatus::Lose, None);
+            msg::send(key.0, game_over, 0).expect("发送消息失败");
+        } else if is_over && key.0 != winner {
+            request_answer(game_session, key);
+        }
+    }
+    for player in players.into_iter().filter(|player| *player != winner) {
+        msg::send(
+            player,
+            GameSessionEvent::RaceFinished { race_id, winner },
+            0,
+        )
+        .expect("发送消息失败");
+    }
+}
+
+// Restart 的第一步：放弃旧会话，返回新会话使用的后端游戏
+fn restart_session(
+    game_session: &mut GameSession,
+    session_id: MessageId,
+) -> Result<ActorId, GameSessionError> {
+    let user = msg::source();
+    let game_program_id = game_session
+        .sessions
+        .get(&(user, session_id))
+        .ok_or(GameSessionError::NotInGame)?
+        .game_program_id;
+    // 被唤醒重新执行时，旧会话已经放弃，新会话已经存在
+    if !game_session.sessions.contains_key(&(user, msg::id())) {
+        // 先检查能否开始新会话，失败时旧会话保持不变
+        check_new_session(game_session, game_program_id, Some((user, session_id)))?;
+        abandon_session(game_session, user, session_id)?;
+    }
+    Ok(game_program_id)
+}
+
+fn check_admin(game_session: &GameSession) -> Result<(), GameSessionError> {
+    if msg::source() == game_session.admin {
+        Ok(())
+    } else {
+        Err(GameSessionError::NotAdmin)
+    }
+}
+
+// 发放奖励，失败时记录下来等待重试，而不是让整个消息失败。
+// `key` 为 (玩家, 会话或重试消息的ID)，同一玩家的多笔奖励可以同时发放
+fn pay_reward(game_session: &mut GameSession, key: (ActorId, MessageId), amount: u128) {
+    let user = key.0;
+    if amount == 0 {
+        return;
+    }
+    let Some(ft_program_id) = game_session.ft_program_id else {
+        // 以原生代币发放
+        if amount <= game_session.pool
+            && msg::send(user, GameSessionEvent::RewardPaid { amount }, amount).is_ok()
+        {
+            game_session.pool -= amount;
+        } else {
+            *game_session.pending_payouts.entry(user).or_default() += amount;
+        }
+        return;
+    };
+
+    // 以代币发放：异步发送 Transfer，在 handle_reply 中处理代币程序的回复
+    let transfer = FtAction::Transfer {
+        from: exec::program_id(),
+        to: user,
+        amount,
+    };
+    match msg::send(ft_program_id, transfer, 0) {
+        Ok(msg_id) => {
+            game_session
+                .pending_rewards
+                .insert(key, PendingReward { msg_id, amount });
+            game_session.reward_messages.insert(msg_id, key);
+        }
+        Err(_) => *game_session.pending_payouts.entry(user).or_default() += amount,
+    }
+}
+
+// 处理代币程序对 Transfer 的回复
+fn handle_reward_reply(game_session: &mut GameSession, key: (ActorId, MessageId)) {
+    let Some(PendingReward { amount, .. }) = game_session.pending_rewards.remove(&key) else {
+        return;
+    };
+    let user = key.0;
+    let is_success = msg::reply_code()
+        .map(|code| code.is_success())
+        .unwrap_or(false);
+    if is_success {
+        msg::send(user, GameSessionEvent::RewardPaid { amount }, 0).expect("发送消息失败");
+    } else {
+        *game_session.pending_payouts.entry(user).or_default() += amount;
+    }
+}
+
+// 导出状态后会话、竞速和挑战由新程序继续
+fn check_not_migrated(game_session: &GameSession) -> Result<(), GameSessionError> {
+    if game_session.migrated {
+        Err(GameSessionError::Migrated)
+    } else {
+        Ok(())
+    }
+}
+
+// 导入完成前新程序的状态不完整，会话、竞速和挑战要等恢复运行后才能继续
+fn check_not_importing(game_session: &GameSession) -> Result<(), GameSessionError> {
+    if game_session.importing.is_some() {
+        Err(GameSessionError::Importing)
+    } else {
+        Ok(())
+    }
+}
+
+// 新会话：没有迁移和暂停，后端游戏必须已注册且没有被其他会话占用，附带的金额必须等于入场费。
+// `replaced` 为即将被放弃、不再占用后端游戏的会话
+fn check_new_session(
+    game_session: &GameSession,
+    game_program_id: ActorId,
+    replaced: Option<(ActorId, MessageId)>,
+) -> Result<GameKind, GameSessionError> {
+    check_not_migrated(game_session)?;
+    if game_session.paused {
+        return Err(GameSessionError::Paused);
+    }
+    let game = *game_session
+        .games
+        .get(&game_program_id)
+        .ok_or(GameSessionError::GameNotRegistered)?;
+    let is_busy = !game.supports_concurrent_sessions()
+        && game_session.sessions.iter().any(|(key, s)| {
+            Some(*key) != replaced && s.game_program_id == game_program_id && s.is_active()
+        });
+    if is_busy {
+        return Err(GameSessionError::GameBusy);
+    }
+    if msg::value() != game_session.reward_config.entry_fee {
+        return Err(GameSessionError::WrongEntryFee);
+    }
+    Ok(game)
+}
+
+// 找到会话动作对应的会话，StartGame 以自身的消息ID 作为新会话的 session_id
+fn open_session(
+    game_session: &mut GameSession,
+    game_session_action: &GameSessionAction,
+) -> Result<(ActorId, MessageId), GameSessionError> {
+    check_not_migrated(game_session)?;
+    check_not_importing(game_session)?;
+    let user = msg::source(); // 获取消息发送者，即玩家
+    let (key, game_program_id) = match game_session_action {
+        GameSessionAction::CheckWord { session_id, .. }
+        | GameSessionAction::Play { session_id, .. } => ((user, *session_id), None),
+        GameSessionAction::StartGameWith { program_id } => ((user, msg::id()), Some(*program_id)),
+        _ => ((user, msg::id()), Some(game_session.wordle_program_id)),
+    };
+    let (race_id, challenge_id) = match game_session_action {
+        GameSessionAction::StartRaceGame { race_id } => (Some(*race_id), None),
+        GameSessionAction::AcceptChallenge { challenge_id } => (None, Some(*challenge_id)),
+        _ => (None, None),
+    };
+    match game_program_id {
+        Some(game_program_id) if !game_session.sessions.contains_key(&key) => {
+            if let Some(race_id) = race_id {
+                check_race_session(game_session, user, race_id)?;
+            }
+            if let Some(challenge_id) = challenge_id {
+                check_challenge(game_session, user, challenge_id)?;
+            }
+            let game = check_new_session(game_session, game_program_id, None)?;
+            // 顺便清理该玩家超过保留期的会话
+            let pruned = game_session.prune_sessions(exec::block_height(), Some(user), u32::MAX);
+            forget_in_wordle(game_session, &pruned, &[]);
+            let session_info = SessionInfo {
+                game,
+                game_program_id,
+                race_id,
+                challenge_id,
+                ..Default::default()
+            };
+            game_session.sessions.insert(key, session_info);
+            if let Some(race) = race_id.and_then(|race_id| game_session.races.get_mut(&race_id)) {
+                race.sessions.push(key);
+            }
+            if let Some(challenge) =
+                challenge_id.and_then(|challenge_id| game_session.challenges.get_mut(&challenge_id))
+            {
+                challenge.session_id = Some(key.1);
+            }
         }
+        Some(_) => {}
+        None if !game_session.sessions.contains_key(&key) => {
+            return Err(GameSessionError::NotInGame);
+        }
+        // 导入的会话在玩家的下一个动作时安排超时检查
+        None => schedule_imported_check(game_session, key),
+    }
+    Ok(key)
+}
+
+// 已向后端游戏发送开始游戏的消息 `request_id`：收取入场费，初始化会话并安排超时检查
+fn begin_session(game_session: &mut GameSession, key: (ActorId, MessageId), request_id: MessageId) {
+    let session_id = key.1;
+    let config = game_session.config.clone();
+    let entry_fee = game_session.reward_config.entry_fee; // 已在创建会话时检查
+    game_session.game_messages.insert(request_id, key);
+    let session_info = game_session.sessions.get_mut(&key).expect("用户不在游戏中");
+    session_info.session_id = session_id; // 保存当前消息ID
+    session_info.original_msg_id = msg::id(); // 保存初始消息ID
+    session_info.send_to_wordle_msg_id = request_id; // 保存发送到Wordle的消息ID
+    session_info.tries = 0; // 初始化尝试次数
+    session_info.start_block = exec::block_height();
+    // 尝试次数限制只适用于 Wordle，其他游戏由后端判定结束
+    session_info.tries_limit = match session_info.game {
+        GameKind::Wordle => config.tries_limit,
+        _ => 0,
+    };
+    session_info.pending_word = None;
+    session_info.guesses.clear();
+    session_info.keyboard = Keyboard::default();
+    session_info.session_status = SessionStatus::WaitWordleStartReply; // 更新状态为等待Wordle启动回复
+    game_session.pool += entry_fee;
+    schedule_check(game_session, key, config.timeout_blocks);
+}
+
+// 在 `delay` 个区块后检查会话是否超时。
+// 优先使用程序预留的 gas，没有可用的预留时才使用当前消息的 gas
+fn schedule_check(game_session: &mut GameSession, key: (ActorId, MessageId), delay: u32) {
+    let (user, session_id) = key;
+    let check_status = GameSessionAction::CheckGameStatus { user, session_id };
+    match game_session.take_reservation(exec::block_height()) {
+        Some(reservation) => msg::send_delayed_from_reservation(
+            reservation.id,
+            exec::program_id(),
+            check_status,
+            0,
+            delay,
+        ),
+        None => msg::send_with_gas_delayed(
+            exec::program_id(),
+            check_status,
+            game_session.config.check_status_gas,
+            0,
+            delay,
+        ),
     }
+    .expect("发送延迟消息失败");
+}
+
+// 旧程序安排的超时检查不会发给新程序，为导入的进行中的会话重新安排，保持原来的到期区块
+fn schedule_imported_check(game_session: &mut GameSession, key: (ActorId, MessageId)) {
+    if !game_session.unscheduled_checks.remove(&key) {
+        return;
+    }
+    let Some(session_info) = game_session.sessions.get(&key).filter(|s| s.is_active()) else {
+        return;
+    };
+    let deadline = session_info
+        .start_block
+        .saturating_add(game_session.config.timeout_blocks);
+    let delay = deadline.saturating_sub(exec::block_height()).max(1);
+    schedule_check(game_session, key, delay);
+}
+
+// 收到后端游戏开始的回复，通知玩家游戏已启动
+fn game_started(game_session: &mut GameSession, key: (ActorId, MessageId)) {
+    if race_finished(game_session, key) {
+        // 等待开始期间竞速已经结束
+        let game_over = end_session(game_session, key, GameStatus::Lose, None);
+        msg::reply(game_over, 0).expect("回复消息失败");
+        return;
+    }
+    let session_info = game_session.sessions.get_mut(&key).expect("用户不在游戏中");
+    msg::reply(GameSessionEvent::StartSuccess { session_id: key.1 }, 0).expect("回复消息失败");
+    session_info.session_status = SessionStatus::WaitUserInput; // 更新状态为等待玩家输入
+}
+
+// 校验玩家的输入，返回该输入和发给后端游戏的消息
+fn move_request(
+    session_info: &SessionInfo,
+    player: ActorId,
+    game_session_action: GameSessionAction,
+) -> Result<(GameInput, Vec<u8>), GameSessionError> {
+    let input = match game_session_action {
+        GameSessionAction::CheckWord { word, .. } => GameInput::Word(word),
+        GameSessionAction::Play { input, .. } => input,
+        _ => unreachable!("只有 CheckWord 和 Play 会发送操作"),
+    };
+    if let GameInput::Word(word) = &input {
+        check_word(word)?;
+    }
+    let payload = session_info
+        .game
+        .move_message(player, &input)
+        .ok_or(GameSessionError::UnsupportedInput)?;
+    Ok((input, payload))
+}
+
+// 已向后端游戏发送玩家的操作 `request_id`，等待结果
+fn move_sent(
+    game_session: &mut GameSession,
+    key: (ActorId, MessageId),
+    request_id: MessageId,
+    input: GameInput,
+) {
+    game_session.game_messages.insert(request_id, key);
+    let session_info = game_session.sessions.get_mut(&key).expect("用户不在游戏中");
+    session_info.pending_word = match input {
+        GameInput::Word(word) => Some(word),
+        _ => None,
+    };
+    session_info.original_msg_id = msg::id();
+    session_info.send_to_wordle_msg_id = request_id;
+    session_info.session_status = SessionStatus::WaitWordleCheckWordReply; // 更新状态为等待Wordle检查单词回复
+}
+
+// 收到操作的结果，计入尝试次数并回复玩家
+fn move_checked(game_session: &mut GameSession, key: (ActorId, MessageId), reply: GameReply) {
+    let race_finished = race_finished(game_session, key);
+    let session_info = game_session.sessions.get_mut(&key).expect("用户不在游戏中");
+    let result = reply.move_result().cloned().expect("回复中没有操作结果");
+    let (status, answer) = match reply {
+        GameReply::Won(..) => (Some(GameStatus::Win), None),
+        GameReply::Lost(_, answer) => (Some(GameStatus::Lose), answer),
+        GameReply::Started | GameReply::Moved(..) => (None, None),
+    };
+    session_info.tries = session_info.tries.saturating_add(1); // 增加尝试次数
+    if let MoveResult::Word {
+        correct_positions,
+        contained_in_word,
+    } = &result
+    {
+        let word = session_info.pending_word.take().unwrap_or_default();
+        session_info.record_guess(GuessRecord {
+            word,
+            correct_positions: correct_positions.clone(),
+            contained_in_word: contained_in_word.clone(),
+        });
+    }
+    // 猜对时最后一次猜测即为答案
+    let answer = match status {
+        Some(GameStatus::Win) => session_info.guesses.last().map(|guess| guess.word.clone()),
+        _ => answer,
+    };
+    let status = if race_finished {
+        // 等待结果期间其他玩家已经赢得竞速
+        Some(GameStatus::Lose)
+    } else {
+        // 后端没有判定结束时，达到尝试次数限制也算失败
+        status.or_else(|| {
+            (session_info.tries == session_info.tries_limit).then_some(GameStatus::Lose)
+        })
+    };
+    match status {
+        Some(GameStatus::Win) => {
+            // 如果猜对了单词，游戏结束并设置状态为胜利
+            let reward = game_session.reward_config.reward_for(session_info.tries);
+            let race_id = session_info.race_id;
+            let game_over = end_session(game_session, key, GameStatus::Win, answer);
+            msg::reply(game_over, 0).expect("回复消息失败");
+            pay_reward(game_session, key, reward);
+            if let Some(race_id) = race_id {
+                finish_race(game_session, race_id, key.0);
+            }
+        }
+        Some(GameStatus::Lose) => {
+            // 游戏结束并设置状态为失败
+            let game_over = end_session(game_session, key, GameStatus::Lose, answer);
+            msg::reply(game_over, 0).expect("回复消息失败");
+        }
+        None => {
+            msg::reply(session_info.move_event(result), 0).expect("回复消息失败");
+            session_info.session_status = SessionStatus::WaitUserInput; // 更新状态为等待玩家输入
+        }
+    }
+}
+
+// 后端游戏没有按时回复，恢复到请求之前的状态，之后收到的回复会被忽略
+fn backend_unavailable(game_session: &mut GameSession, key: (ActorId, MessageId)) {
+    let session_info = game_session.sessions.get_mut(&key).expect("用户不在游戏中");
+    game_session
+        .game_messages
+        .remove(&session_info.send_to_wordle_msg_id);
+    let refund = match session_info.session_status {
+        SessionStatus::WaitWordleStartReply => {
+            // 游戏没有开始，删除会话并退还入场费，玩家可以重新开始或重新接受挑战
+            if let Some(challenge) = session_info
+                .challenge_id
+                .and_then(|challenge_id| game_session.challenges.get_mut(&challenge_id))
+            {
+                challenge.session_id = None;
+            }
+            if let Some(race) = session_info
+                .race_id
+                .and_then(|race_id| game_session.races.get_mut(&race_id))
+            {
+                race.sessions.retain(|race_key| *race_key != key);
+            }
+            game_session.sessions.remove(&key);
+            msg::value()
+        }
+        _ => {
+            // 本次猜测不计数，玩家可以重新猜测
+            session_info.pending_word = None;
+            session_info.session_status = SessionStatus::WaitUserInput;
+            0
+        }
+    };
+    // 等待期间入场费可能已被管理员提取，这时记为待发放，资金补充后用 RetryPayout 领取
+    let refund = match game_session.pool.checked_sub(refund) {
+        Some(pool) => {
+            game_session.pool = pool;
+            refund
+        }
+        None => {
+            *game_session.pending_payouts.entry(key.0).or_default() += refund;
+            0
+        }
+    };
+    msg::reply(
+        GameSessionEvent::Error(GameSessionError::WordleUnavailable),
+        refund,
+    )
+    .expect("回复消息失败");
+}
+
+// 结束会话并归档，返回发给玩家的 GameOver。
+// 答案未知时先让后端结束游戏再请求答案，收到后通过 AnswerRevealed 发给玩家。
+// 竞速进行中失败的会话不公开答案，等竞速结束时再请求
+fn end_session(
+    game_session: &mut GameSession,
+    key: (ActorId, MessageId),
+    status: GameStatus,
+    answer: Option<String>,
+) -> GameSessionEvent {
+    let (user, session_id) = key;
+    let hide_answer = status == GameStatus::Lose && race_running(game_session, key);
+    let session_info = game_session.sessions.get_mut(&key).expect("用户不在游戏中");
+    let board = session_info.guesses.iter().map(BoardRow::from).collect();
+    if answer.is_none() {
+        // 后端的游戏可能已经结束，这时 EndGame 失败，不影响之后的 Reveal
+        if let Some(payload) = session_info.game.end_message(wordle_player(session_id)) {
+            if let Ok(msg_id) = msg::send_bytes(session_info.game_program_id, payload, 0) {
+                game_session.cleanup_messages.insert(msg_id);
+            }
+        }
+    }
+    let answer = answer.filter(|_| !hide_answer);
+    let record = session_info.finish(status.clone(), answer.clone(), exec::block_height());
+    let challenge_id = session_info.challenge_id;
+    let game = session_info.game;
+    if answer.is_none() && !hide_answer {
+        request_answer(game_session, key);
+    }
+    // 接受挑战的会话把结果发给挑战者
+    if let Some(challenge_id) = challenge_id {
+        if let Some(challenge) = game_session.challenges.remove(&challenge_id) {
+            msg::send(
+                challenge.challenger,
+                GameSessionEvent::ChallengeFinished {
+                    challenge_id,
+                    opponent: user,
+                    status: status.clone(),
+                },
+                0,
+            )
+            .expect("发送消息失败");
+        }
+    }
+    game_session.archive_session(user, record, game);
+    GameSessionEvent::GameOver {
+        session_id,
+        status,
+        answer,
+        board,
+    }
+}
+
+// 向后端请求已结束会话的答案，请求失败时答案保持未知，不影响会话结束
+fn request_answer(game_session: &mut GameSession, key: (ActorId, MessageId)) {
+    let session_info = &game_session.sessions[&key];
+    if let Some(payload) = session_info.game.reveal_message(wordle_player(key.1)) {
+        if let Ok(msg_id) = msg::send_bytes(session_info.game_program_id, payload, 0) {
+            game_session.reveal_messages.insert(msg_id, key);
+        }
+    }
+}
+
+// 放弃会话，之后该会话的 CheckGameStatus 消息会被忽略
+fn abandon_session(
+    game_session: &mut GameSession,
+    user: ActorId,
+    session_id: MessageId,
+) -> Result<GameSessionEvent, GameSessionError> {
+    let session_info = game_session
+        .sessions
+        .get(&(user, session_id))
+        .ok_or(GameSessionError::NotInGame)?;
+    let action = GameSessionAction::Abandon { session_id };
+    match session_info.session_status.transition(&action, false) {
+        Transition::Abandon => {}
+        Transition::Reject(error) => return Err(error),
+        _ => unreachable!("Abandon 只会被接受或拒绝"),
+    }
+    Ok(end_session(
+        game_session,
+        (user, session_id),
+        GameStatus::Lose,
+        None,
+    ))
+}
+
+fn check_game_status(game_session: &mut GameSession, user: ActorId, session_id: MessageId) {
+    let Some(session_info) = game_session.sessions.get_mut(&(user, session_id)) else {
+        return;
+    };
+    let action = GameSessionAction::CheckGameStatus { user, session_id };
+    if session_info.session_status.transition(&action, false) == Transition::Timeout {
+        // 如果时间到未完成，游戏结束并设置状态为失败，之后收到的 Wordle 回复会被忽略
+        game_session
+            .game_messages
+            .remove(&session_info.send_to_wordle_msg_id);
+        let game_over = end_session(game_session, (user, session_id), GameStatus::Lose, None);
+        msg::send(user, game_over, 0).expect("发送消息失败");
+    }
+}
+
+// 处理后端对请求答案的回复，把答案记入归档并发给玩家
+fn handle_reveal_reply(
+    game_session: &mut GameSession,
+    reply_to: MessageId,
+    source: ActorId,
+) -> Option<AnomalyKind> {
+    let key = game_session.reveal_messages[&reply_to];
+    let (user, session_id) = key;
+    let session_info = game_session.sessions.get(&key).expect("用户不在游戏中");
+    if source != session_info.game_program_id {
+        return Some(AnomalyKind::UnexpectedSource);
+    }
+    game_session.reveal_messages.remove(&reply_to);
+    if !msg::reply_code()
+        .map(|code| code.is_success())
+        .unwrap_or(false)
+    {
+        return Some(AnomalyKind::ErrorReply);
+    }
+    let Some(answer) = msg::load_bytes()
+        .ok()
+        .and_then(|payload| session_info.game.decode_answer(&payload))
+    else {
+        return Some(AnomalyKind::Undecodable);
+    };
+    if let Some(record) = game_session
+        .archive
+        .get_mut(&user)
+        .and_then(|sessions| sessions.iter_mut().find(|s| s.session_id == session_id))
+    {
+        record.answer = Some(answer.clone());
+    }
+    msg::send(
+        user,
+        GameSessionEvent::AnswerRevealed { session_id, answer },
+        0,
+    )
+    .expect("发送消息失败");
+    None
+}
+
+// 异常回复的记录
+fn anomaly(reply_to: MessageId, source: ActorId, kind: AnomalyKind) -> ReplyAnomaly {
+    ReplyAnomaly {
+        reply_to,
+        source,
+        block: exec::block_height(),
+        kind,
+    }
+}
+
+// 让 Wordle 删除已清理的会话和竞速留下的记录：玩家最近结束的游戏和竞速共用的单词。
+// 玩家在同一个 Wordle 程序上还有其他会话时保留，这些会话可能还要请求答案
+fn forget_in_wordle(
+    game_session: &mut GameSession,
+    sessions: &[((ActorId, MessageId), SessionInfo)],
+    races: &[(u64, Race)],
+) {
+    let mut forget: HashMap<ActorId, (Vec<ActorId>, Vec<u64>)> = HashMap::new();
+    for ((user, _), session_info) in sessions {
+        let program_id = session_info.game_program_id;
+        let has_other_session = game_session
+            .sessions
+            .iter()
+            .any(|((player, _), other)| player == user && other.game_program_id == program_id);
+        if session_info.game != GameKind::Wordle || has_other_session {
+            continue;
+        }
+        let (users, _) = forget.entry(program_id).or_default();
+        if !users.contains(user) {
+            users.push(*user);
+        }
+    }
+    // 没有开始过的竞速在 Wordle 中没有单词，从旧程序迁移的竞速的单词属于旧程序
+    let shared_ids: Vec<_> = races
+        .iter()
+        .filter(|(_, race)| !race.sessions.is_empty() && race.word_owner.is_none())
+        .map(|(race_id, _)| *race_id)
+        .collect();
+    if !shared_ids.is_empty() {
+        forget
+            .entry(game_session.wordle_program_id)
+            .or_default()
+            .1
+            .extend(shared_ids);
+    }
+    for (program_id, (users, shared_ids)) in forget {
+        // 清理失败不影响会话
+        if let Ok(msg_id) = msg::send(program_id, WordleAction::Forget { users, shared_ids }, 0) {
+            game_session.cleanup_messages.insert(msg_id);
+        }
+    }
+}
+
+// 处理不属于会话请求的回复：奖励发放、请求答案和清理。返回该回复是否已处理
+fn handle_side_reply(game_session: &mut GameSession, reply_to: MessageId, source: ActorId) -> bool {
+    if game_session.cleanup_messages.remove(&reply_to) {
+        return true;
+    }
+    if let Some(&key) = game_session.reward_messages.get(&reply_to) {
+        if Some(source) != game_session.ft_program_id {
+            game_session.record_anomaly(anomaly(reply_to, source, AnomalyKind::UnexpectedSource));
+            return true;
+        }
+        game_session.reward_messages.remove(&reply_to);
+        handle_reward_reply(game_session, key);
+        return true;
+    }
+    if game_session.reveal_messages.contains_key(&reply_to) {
+        if let Some(kind) = handle_reveal_reply(game_session, reply_to, source) {
+            game_session.record_anomaly(anomaly(reply_to, source, kind));
+        }
+        return true;
+    }
+    false
 }
 
 #[no_mangle]
 extern "C" fn state() {
+    let query: StateQuery = msg::load().expect("无法解码 StateQuery");
     let game_session = get_game_session();
-    msg::reply::<GameSessionState>(game_session.into(), 0) 。expect("状态查询回复失败");
+    let reply = match query {
+        StateQuery::Full => StateReply::Full(game_session.into()),
+        StateQuery::Archive {
+            user,
+            offset,
+            limit,
+        } => {
+            let archive = game_session
+                .archive
+                .get(&user)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            StateReply::Archive {
+                total: archive.len() as u32,
+                sessions: archive
+                    .iter()
+                    .skip(offset as usize)
+                    .take(limit as usize)
+                    .cloned()
+                    .collect(),
+            }
+        }
+        StateQuery::PlayerStats(user) => {
+            StateReply::PlayerStats(game_session.stats.get(&user).cloned())
+        }
+        StateQuery::Leaderboard { limit } => {
+            StateReply::Leaderboard(game_session.leaderboard(limit))
+        }
+        StateQuery::Sessions {
+            user,
+            offset,
+            limit,
+        } => {
+            let mut keys: Vec<_> = game_session
+                .sessions
+                .keys()
+                .filter(|(player, _)| user.is_none() || user == Some(*player))
+                .collect();
+            keys.sort();
+            StateReply::Sessions {
+                total: keys.len() as u32,
+                sessions: keys
+                    .into_iter()
+                    .skip(offset as usize)
+                    .take(limit as usize)
+                    .map(|key| (key.0, game_session.sessions[key].clone()))
+                    .collect(),
+            }
+        }
+    };
+    msg::reply(reply, 0).expect("状态查询回复失败");
 }
 
-
 fn get_game_session_mut() -> &'static mut GameSession {
-    unsafe {
-        GAME_SESSION_STATE
-            。as_mut()
-            。expect("游戏会话未初始化")
-    }
+    unsafe { GAME_SESSION_STATE.as_mut().expect("游戏会话未初始化") }
 }
 fn get_game_session() -> &'static GameSession {
-    unsafe {
-        GAME_SESSION_STATE
-            。as_ref()
-            。expect("游戏会话未初始化")
-    }
+    unsafe { GAME_SESSION_STATE.as_ref().expect("游戏会话未初始化") }
 }
@@ -0,0 +1,136 @@
+//! 手写的请求/回复流程：发送请求后用 `exec::wait_for` 挂起消息，
+//! 在 `handle_reply` 中记下回复并用 `exec::wake` 唤醒，消息被重新执行时
+//! 由 `SessionStatus` 的状态转换表决定下一步。
+
+use super::*;
+
+#[no_mangle]
+extern "C" fn handle() {
+    // 解码并处理游戏会话动作
+    let game_session_action: GameSessionAction = msg::load().expect("无法解码 GameSessionAction");
+    let game_session = get_game_session_mut();
+
+    let result = match game_session_action {
+        GameSessionAction::StartGame
+        | GameSessionAction::CheckWord { .. }
+        | GameSessionAction::StartGameWith { .. }
+        | GameSessionAction::Play { .. }
+        | GameSessionAction::StartRaceGame { .. }
+        | GameSessionAction::AcceptChallenge { .. } => {
+            handle_session_action(game_session, game_session_action)
+        }
+        // 放弃和开始在同一条消息中完成
+        GameSessionAction::Restart { session_id } => restart_session(game_session, session_id)
+            .and_then(|program_id| {
+                handle_session_action(
+                    game_session,
+                    GameSessionAction::StartGameWith { program_id },
+                )
+            }),
+        game_session_action => process_action(game_session, game_session_action),
+    };
+    reply_result(result);
+}
+
+fn handle_session_action(
+    game_session: &mut GameSession,
+    game_session_action: GameSessionAction,
+) -> Result<(), GameSessionError> {
+    let key = open_session(game_session, &game_session_action)?;
+    let reply_timeout_blocks = game_session.config.reply_timeout_blocks;
+    let session_info = game_session.sessions.get_mut(&key).expect("用户不在游戏中");
+    // 只有正在等待回复的那条消息被唤醒后才能处理收到的回复
+    let is_waiting_msg = msg::id() == session_info.original_msg_id;
+    let transition = session_info
+        .session_status
+        .transition(&game_session_action, is_waiting_msg);
+
+    match transition {
+        Transition::StartGame => {
+            // 向后端游戏程序发送开始游戏的消息
+            let game_program_id = session_info.game_program_id;
+            let payload = game_session.start_message(key, wordle_player(key.1));
+            let request_id = msg::send_bytes(game_program_id, payload, 0).expect("发送消息失败");
+            begin_session(game_session, key, request_id);
+            exec::wait_for(reply_timeout_blocks); // 等待回复，超时后重新执行本消息
+        }
+        Transition::GameStarted => game_started(game_session, key),
+        Transition::SendGuess => {
+            let (input, payload) =
+                move_request(session_info, wordle_player(key.1), game_session_action)?;
+            let request_id =
+                msg::send_bytes(session_info.game_program_id, payload, 0).expect("发送消息失败");
+            move_sent(game_session, key, request_id, input);
+            exec::wait_for(reply_timeout_blocks); // 等待回复，超时后重新执行本消息
+        }
+        Transition::GuessChecked => {
+            let SessionStatus::ReplyReceived(reply) = &session_info.session_status else {
+                unreachable!("只有收到回复后才能处理猜测结果");
+            };
+            let reply = reply.clone();
+            move_checked(game_session, key, reply);
+        }
+        Transition::WordleUnavailable => backend_unavailable(game_session, key),
+        Transition::Timeout | Transition::Ignore | Transition::Abandon => {
+            unreachable!("CheckGameStatus、Abandon 和 Restart 已单独处理");
+        }
+        Transition::Reject(error) => return Err(error),
+    }
+    Ok(())
+}
+
+#[no_mangle]
+extern "C" fn handle_reply() {
+    let reply_to = msg::reply_to().expect("查询 reply_to 数据失败");
+    let source = msg::source();
+    if source == exec::program_id() {
+        // 延迟的 CheckGameStatus 消息的自动回复
+        return;
+    }
+    let game_session = get_game_session_mut();
+    if handle_side_reply(game_session, reply_to, source) {
+        return;
+    }
+    // 按消息ID 找到会话，而不是按回复中的玩家
+    let Some(session_info) = game_session
+        .game_messages
+        .get(&reply_to)
+        .and_then(|key| game_session.sessions.get_mut(key))
+    else {
+        game_session.record_anomaly(anomaly(reply_to, source, AnomalyKind::UnknownMessage));
+        return;
+    };
+    if source != session_info.game_program_id {
+        game_session.record_anomaly(anomaly(reply_to, source, AnomalyKind::UnexpectedSource));
+        return;
+    }
+    game_session.game_messages.remove(&reply_to);
+
+    // 由会话的后端游戏解码回复
+    let is_success = msg::reply_code()
+        .map(|code| code.is_success())
+        .unwrap_or(false);
+    let is_start = matches!(
+        session_info.session_status,
+        SessionStatus::WaitWordleStartReply
+    );
+    let reply = msg::load_bytes()
+        .ok()
+        .and_then(|payload| session_info.game.decode_reply(&payload, is_start));
+    let kind = match reply {
+        // 只接受类型相符的回复，避免把开始游戏的回复当成操作结果
+        Some(reply) if is_success && session_info.session_status.expects_reply(&reply) => {
+            session_info.session_status = SessionStatus::ReplyReceived(reply); // 收到后端游戏的回复
+            exec::wake(session_info.original_msg_id).expect("唤醒消息失败");
+            return;
+        }
+        _ if !is_success => AnomalyKind::ErrorReply,
+        None => AnomalyKind::Undecodable,
+        Some(_) => AnomalyKind::UnexpectedReply,
+    };
+    // 不再等待，立即唤醒等待中的消息，按超时处理
+    if session_info.is_wait_reply_status() {
+        exec::wake(session_info.original_msg_id).expect("唤醒消息失败");
+    }
+    game_session.record_anomaly(anomaly(reply_to, source, kind));
+}
@@ -1,9 +1,191 @@
 use game_session_io::*;
-use gtest::{Log, ProgramBuilder, System};
+use gstd::{ActorId, MessageId};
+use gtest::{Log, Program, ProgramBuilder, RunResult, System, WasmProgram};
+use parity_scale_codec::{Decode, Encode};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use wordle_io::{StateQuery as WordleStateQuery, StateReply as WordleStateReply, WordleInit};
 
 const GAME_SESSION_PROGRAM_ID: u64 = 1;
 const WORDLE_PROGRAM_ID: u64 = 2;
 const USER: u64 = 50; // 学号为 50
+const WORDLE_SEED: u64 = 1; // 使用该种子时第一局的单词为 "horse"
+const WORDLE_MAX_ATTEMPTS: u8 = 5;
+
+// 部署并初始化 wordle 和 game_session 程序，返回 game_session 程序
+fn setup(system: &System) -> Program<'_> {
+    setup_with_ft(system, None)
+}
+
+// 动作以错误回复失败，而不是让消息执行失败
+fn assert_error(res: &RunResult, user: u64, error: GameSessionError) {
+    let log = Log::builder()
+        .dest(user)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Error(error));
+    assert!(!res.main_failed() && res.contains(&log));
+}
+
+// 由每次猜测的单词和结果得到棋盘
+fn board(guesses: &[(&str, &[u8], &[u8])]) -> Vec<BoardRow> {
+    guesses
+        .iter()
+        .map(|(word, correct_positions, contained_in_word)| {
+            BoardRow::from(&GuessRecord {
+                word: word.to_string(),
+                correct_positions: correct_positions.to_vec(),
+                contained_in_word: contained_in_word.to_vec(),
+            })
+        })
+        .collect()
+}
+
+// 由每次猜测的单词和结果汇总键盘状态
+fn keyboard(guesses: &[(&str, &[u8], &[u8])]) -> Keyboard {
+    let mut keyboard = Keyboard::default();
+    for row in board(guesses) {
+        keyboard.record(&row);
+    }
+    keyboard
+}
+
+// 构造游戏结束事件
+fn game_over(
+    session_id: MessageId,
+    status: GameStatus,
+    answer: Option<&str>,
+    guesses: &[(&str, &[u8], &[u8])],
+) -> GameSessionEvent {
+    GameSessionEvent::GameOver {
+        session_id,
+        status,
+        answer: answer.map(str::to_string),
+        board: board(guesses),
+    }
+}
+
+fn setup_with_ft(system: &System, ft_program_id: Option<u64>) -> Program<'_> {
+    let game_session_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(GAME_SESSION_PROGRAM_ID)
+            .build(system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(system);
+
+    let res = wordle_program.send(
+        USER,
+        WordleInit {
+            seed: Some(WORDLE_SEED),
+            max_attempts: WORDLE_MAX_ATTEMPTS,
+            trusted_programs: vec![GAME_SESSION_PROGRAM_ID.into()],
+        },
+    );
+    assert!(!res.main_failed());
+
+    let res = game_session_program.send(
+        USER,
+        GameSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: ft_program_id.map(Into::into),
+            import: None,
+            config: GameSessionConfig::default(),
+        },
+    );
+    assert!(!res.main_failed());
+    game_session_program
+}
+
+// 模拟的同质化代币程序，只支持 Transfer，余额不足时失败
+#[derive(Debug)]
+struct MockFt {
+    balances: Arc<Mutex<HashMap<ActorId, u128>>>,
+}
+
+impl WasmProgram for MockFt {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let FtAction::Transfer { from, to, amount } =
+            FtAction::decode(&mut &payload[..]).map_err(|_| "无法解码 FtAction")?
+        else {
+            return Err("不支持的操作");
+        };
+        let mut balances = self.balances.lock().unwrap();
+        let from_balance = balances.entry(from).or_default();
+        if *from_balance < amount {
+            return Err("余额不足");
+        }
+        *from_balance -= amount;
+        *balances.entry(to).or_default() += amount;
+        Ok(Some(FtEvent::Transfer { from, to, amount }.encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+// 读取完整的游戏会话状态
+fn full_state(game_session_program: &Program) -> GameSessionState {
+    let StateReply::Full(state) = game_session_program.read_state(StateQuery::Full).unwrap() else {
+        panic!("状态查询回复错误");
+    };
+    state
+}
+
+// 分页读取保存的会话，`user` 不为空时只读取该玩家的
+fn sessions(
+    game_session_program: &Program,
+    user: Option<u64>,
+    offset: u32,
+    limit: u32,
+) -> (u32, Vec<(ActorId, SessionInfo)>) {
+    let StateReply::Sessions { total, sessions } = game_session_program
+        .read_state(StateQuery::Sessions {
+            user: user.map(ActorId::from),
+            offset,
+            limit,
+        })
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    (total, sessions)
+}
+
+// 读取玩家已归档的会话
+fn archive(
+    game_session_program: &Program,
+    user: u64,
+    offset: u32,
+    limit: u32,
+) -> (u32, Vec<ArchivedSession>) {
+    let StateReply::Archive { total, sessions } = game_session_program
+        .read_state(StateQuery::Archive {
+            user: user.into(),
+            offset,
+            limit,
+        })
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    (total, sessions)
+}
 
 #[test]
 fn test_win() {
@@ -11,15 +193,24 @@ fn test_win() {
     system.init_logger();
 
     // 部署 game_session 和 wordle 程序
-    let game_session_program = ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
-        .with_id(GAME_SESSION_PROGRAM_ID)
-        .build(&system);
-    let wordle_program = ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
-        .with_id(WORDLE_PROGRAM_ID)
-        .build(&system);
+    let game_session_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(GAME_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
 
     // 初始化 Wordle 程序
-    let res = wordle_program.send_bytes(USER, []);
+    let res = wordle_program.send(
+        USER,
+        WordleInit {
+            seed: Some(WORDLE_SEED),
+            max_attempts: WORDLE_MAX_ATTEMPTS,
+            trusted_programs: vec![GAME_SESSION_PROGRAM_ID.into()],
+        },
+    );
     assert!(!res.main_failed());
 
     // 初始化 GameSession 程序
@@ -27,6 +218,9 @@ fn test_win() {
         USER,
         GameSessionInit {
             wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: None,
+            config: GameSessionConfig::default(),
         },
     );
     assert!(!res.main_failed());
@@ -35,44 +229,57 @@ fn test_win() {
     let res = game_session_program.send(
         USER,
         GameSessionAction::CheckWord {
+            session_id: MessageId::zero(),
             word: "abcde".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert_error(&res, USER, GameSessionError::NotInGame);
 
     // 开始游戏
     let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
     let log = Log::builder()
         .dest(USER)
         .source(GAME_SESSION_PROGRAM_ID)
-        .payload(GameSessionEvent::StartSuccess);
+        .payload(GameSessionEvent::StartSuccess { session_id });
     assert!(!res.main_failed() && res.contains(&log));
 
-    // 再次尝试开始游戏（应该失败，因为游戏已经开始）
+    // 再次开始游戏，玩家可以同时进行多局，每局有各自的 session_id
     let res = game_session_program.send(USER, GameSessionAction::StartGame);
-    assert!(res.main_failed());
+    let other_session_id = res.sent_message_id();
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::StartSuccess {
+            session_id: other_session_id,
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+    assert_ne!(session_id, other_session_id);
 
     // 尝试输入无效单词（不符合规则，应该失败）
     let res = game_session_program.send(
         USER,
         GameSessionAction::CheckWord {
+            session_id,
             word: "Abcde".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert_error(&res, USER, GameSessionError::InvalidWord);
 
     let res = game_session_program.send(
         USER,
         GameSessionAction::CheckWord {
+            session_id,
             word: "abcdef".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert_error(&res, USER, GameSessionError::InvalidWord);
 
     // 输入合法单词并验证结果
     let res = game_session_program.send(
         USER,
         GameSessionAction::CheckWord {
+            session_id,
             word: "house".to_string(),
         },
     );
@@ -82,6 +289,9 @@ fn test_win() {
         .payload(GameSessionEvent::CheckWordResult {
             correct_positions: vec![0, 1, 3, 4],
             contained_in_word: vec![],
+            tries_used: 1,
+            tries_left: 4,
+            keyboard: keyboard(&[("house", &[0, 1, 3, 4], &[])]),
         });
     assert!(!res.main_failed() && res.contains(&log));
 
@@ -89,27 +299,103 @@ fn test_win() {
     let res = game_session_program.send(
         USER,
         GameSessionAction::CheckWord {
+            session_id,
             word: "horse".to_string(),
         },
     );
     let log = Log::builder()
         .dest(USER)
         .source(GAME_SESSION_PROGRAM_ID)
-        .payload(GameSessionEvent::GameOver(GameStatus::Win));
+        .payload(game_over(
+            session_id,
+            GameStatus::Win,
+            Some("horse"),
+            &[
+                ("house", &[0, 1, 3, 4], &[]),
+                ("horse", &[0, 1, 2, 3, 4], &[]),
+            ],
+        ));
     assert!(!res.main_failed() && res.contains(&log));
 
-    // 另一个用户尝试检查单词（没有开始游戏，应该失败）
+    // 结束的会话被归档，记录了每次猜测的结果
+    let (total, sessions) = archive(&game_session_program, USER, 0, 10);
+    assert_eq!(total, 1);
+    assert!(matches!(sessions[0].status, GameStatus::Win));
+    assert_eq!(sessions[0].answer.as_deref(), Some("horse"));
+    assert_eq!(sessions[0].tries, 2);
+    assert_eq!(
+        sessions[0].guesses,
+        vec![
+            GuessRecord {
+                word: "house".to_string(),
+                correct_positions: vec![0, 1, 3, 4],
+                contained_in_word: vec![],
+            },
+            GuessRecord {
+                word: "horse".to_string(),
+                correct_positions: vec![0, 1, 2, 3, 4],
+                contained_in_word: vec![],
+            },
+        ]
+    );
+    assert!(sessions[0].start_block <= sessions[0].end_block);
+
+    // 统计数据和排行榜
+    let blocks_taken = sessions[0].end_block - sessions[0].start_block;
+    assert_eq!(
+        sessions[0].score,
+        win_score(2, GameSessionConfig::default().tries_limit, blocks_taken)
+    );
+    let StateReply::PlayerStats(Some(stats)) = game_session_program
+        .read_state(StateQuery::PlayerStats(USER.into()))
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    assert_eq!(
+        stats,
+        PlayerStats {
+            wins: 1,
+            losses: 0,
+            current_streak: 1,
+            max_streak: 1,
+            guess_distribution: [0, 1, 0, 0, 0],
+            total_score: u64::from(win_score(
+                2,
+                GameSessionConfig::default().tries_limit,
+                blocks_taken
+            )),
+        }
+    );
+    let StateReply::Leaderboard(leaderboard) = game_session_program
+        .read_state(StateQuery::Leaderboard { limit: 10 })
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    assert_eq!(leaderboard, vec![(ActorId::from(USER), stats)]);
+
+    // 另一个用户尝试在该玩家的会话中检查单词（应该失败）
     let res = game_session_program.send(
         51,
         GameSessionAction::CheckWord {
+            session_id,
             word: "abcde".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert_error(&res, 51, GameSessionError::NotInGame);
 
-    // 输出当前游戏状态
-    let state: GameSessionState = game_session_program.read_state(b"").unwrap();
+    // 第二局仍在进行中
+    let state = full_state(&game_session_program);
+    assert_eq!(state.session_count, 2);
     println!("{:?}", state);
+    let (total, user_sessions) = sessions(&game_session_program, Some(USER), 0, 10);
+    assert_eq!(total, 2);
+    assert_eq!(
+        user_sessions.iter().filter(|(_, s)| s.is_active()).count(),
+        1
+    );
+    assert_eq!(sessions(&game_session_program, Some(51), 0, 10).0, 0);
 }
 
 #[test]
@@ -118,15 +404,24 @@ fn test_tried_limit() {
     system.init_logger();
 
     // 部署 game_session 和 wordle 程序
-    let game_session_program = ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
-        .with_id(GAME_SESSION_PROGRAM_ID)
-        .build(&system);
-    let wordle_program = ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
-        .with_id(WORDLE_PROGRAM_ID)
-        .build(&system);
+    let game_session_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(GAME_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
 
     // 初始化 Wordle 程序
-    let res = wordle_program.send_bytes(USER, []);
+    let res = wordle_program.send(
+        USER,
+        WordleInit {
+            seed: Some(WORDLE_SEED),
+            max_attempts: WORDLE_MAX_ATTEMPTS,
+            trusted_programs: vec![GAME_SESSION_PROGRAM_ID.into()],
+        },
+    );
     assert!(!res.main_failed());
 
     // 初始化 GameSession 程序
@@ -134,16 +429,20 @@ fn test_tried_limit() {
         USER,
         GameSessionInit {
             wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: None,
+            config: GameSessionConfig::default(),
         },
     );
     assert!(!res.main_failed());
 
     // 开始游戏
     let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
     let log = Log::builder()
         .dest(USER)
         .source(GAME_SESSION_PROGRAM_ID)
-        .payload(GameSessionEvent::StartSuccess);
+        .payload(GameSessionEvent::StartSuccess { session_id });
     assert!(!res.main_failed() && res.contains(&log));
 
     // 测试猜测次数限制
@@ -151,14 +450,21 @@ fn test_tried_limit() {
         let res = game_session_program.send(
             USER,
             GameSessionAction::CheckWord {
+                session_id,
                 word: "house".to_string(),
             },
         );
         if i == 4 {
+            // Wordle 判定失败时直接给出答案
             let log = Log::builder()
                 .dest(USER)
                 .source(GAME_SESSION_PROGRAM_ID)
-                .payload(GameSessionEvent::GameOver(GameStatus::Lose));
+                .payload(game_over(
+                    session_id,
+                    GameStatus::Lose,
+                    Some("horse"),
+                    &[("house", &[0, 1, 3, 4], &[]); 5],
+                ));
             assert!(!res.main_failed() && res.contains(&log));
         } else {
             let log = Log::builder()
@@ -167,31 +473,72 @@ fn test_tried_limit() {
                 .payload(GameSessionEvent::CheckWordResult {
                     correct_positions: vec![0, 1, 3, 4],
                     contained_in_word: vec![],
+                    tries_used: i + 1,
+                    tries_left: 4 - i,
+                    keyboard: keyboard(&[("house", &[0, 1, 3, 4], &[])]),
                 });
             assert!(!res.main_failed() && res.contains(&log));
         }
     }
+    // 第二局结束后归档中有两条记录，可以分页读取
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    for _ in 0..5 {
+        let res = game_session_program.send(
+            USER,
+            GameSessionAction::CheckWord {
+                session_id,
+                word: "horse".to_string(), // 第二局的单词为 "human"
+            },
+        );
+        assert!(!res.main_failed());
+    }
+    let (total, sessions) = archive(&game_session_program, USER, 1, 10);
+    assert_eq!(total, 2);
+    assert_eq!(sessions.len(), 1);
+    assert!(matches!(sessions[0].status, GameStatus::Lose));
+    assert_eq!(sessions[0].tries, 5);
+    let (_, sessions) = archive(&game_session_program, USER, 0, 1);
+    assert_eq!(sessions[0].guesses.len(), 5);
+    let StateReply::PlayerStats(Some(stats)) = game_session_program
+        .read_state(StateQuery::PlayerStats(USER.into()))
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    assert_eq!((stats.wins, stats.losses, stats.current_streak), (0, 2, 0));
+    assert_eq!(stats.total_score, 0);
+
     // 输出当前游戏状态
-    let state: GameSessionState = game_session_program.read_state(b"").unwrap();
+    let state = full_state(&game_session_program);
     println!("{:?}", state);
 }
 
 #[test]
-#[ignore] // 延迟逻辑的执行
 fn test_delayed_logic() {
     let system = System::new();
     system.init_logger();
 
     // 部署 game_session 和 wordle 程序
-    let game_session_program = ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
-        .with_id(GAME_SESSION_PROGRAM_ID)
-        .build(&system);
-    let wordle_program = ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
-        .with_id(WORDLE_PROGRAM_ID)
-        .build(&system);
+    let game_session_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(GAME_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
 
     // 初始化 Wordle 程序
-    let res = wordle_program.send_bytes(USER, []);
+    let res = wordle_program.send(
+        USER,
+        WordleInit {
+            seed: Some(WORDLE_SEED),
+            max_attempts: WORDLE_MAX_ATTEMPTS,
+            trusted_programs: vec![GAME_SESSION_PROGRAM_ID.into()],
+        },
+    );
     assert!(!res.main_failed());
 
     // 初始化 GameSession 程序
@@ -199,16 +546,20 @@ fn test_delayed_logic() {
         USER,
         GameSessionInit {
             wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: None,
+            config: GameSessionConfig::default(),
         },
     );
     assert!(!res.main_failed());
 
     // 开始游戏
     let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
     let log = Log::builder()
         .dest(USER)
         .source(GAME_SESSION_PROGRAM_ID)
-        .payload(GameSessionEvent::StartSuccess);
+        .payload(GameSessionEvent::StartSuccess { session_id });
     assert!(!res.main_failed() && res.contains(&log));
 
     // 模拟等待200个区块的延迟
@@ -219,10 +570,1802 @@ fn test_delayed_logic() {
     let log = Log::builder()
         .dest(USER)
         .source(GAME_SESSION_PROGRAM_ID)
-        .payload(GameSessionEvent::GameOver(GameStatus::Lose));
-    assert!(result[0].contains(&log));
+        .payload(game_over(session_id, GameStatus::Lose, None, &[]));
+    assert!(result.iter().any(|res| res.contains(&log)));
 
     // 输出当前游戏状态
-    let state: GameSessionState = game_session_program.read_state(b"").unwrap();
+    let state = full_state(&game_session_program);
     println!("{:?}", state);
 }
+
+#[test]
+fn test_config() {
+    let system = System::new();
+    system.init_logger();
+
+    // 部署 game_session 和 wordle 程序
+    let game_session_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(GAME_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // 初始化 Wordle 程序
+    let res = wordle_program.send(
+        USER,
+        WordleInit {
+            seed: Some(WORDLE_SEED),
+            max_attempts: WORDLE_MAX_ATTEMPTS,
+            trusted_programs: vec![GAME_SESSION_PROGRAM_ID.into()],
+        },
+    );
+    assert!(!res.main_failed());
+
+    // 无效的配置（应该失败）
+    let invalid_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(3)
+            .build(&system);
+    let res = invalid_program.send(
+        USER,
+        GameSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: None,
+            config: GameSessionConfig {
+                tries_limit: 0,
+                ..Default::default()
+            },
+        },
+    );
+    assert!(res.main_failed());
+
+    // 初始化 GameSession 程序，最多尝试 2 次
+    let res = game_session_program.send(
+        USER,
+        GameSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: None,
+            config: GameSessionConfig {
+                tries_limit: 2,
+                ..Default::default()
+            },
+        },
+    );
+    assert!(!res.main_failed());
+
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    for i in 0..2 {
+        let res = game_session_program.send(
+            USER,
+            GameSessionAction::CheckWord {
+                session_id,
+                word: "house".to_string(),
+            },
+        );
+        if i == 1 {
+            // 会话判定失败时答案未知，随后从 Wordle 取得
+            let log = Log::builder()
+                .dest(USER)
+                .source(GAME_SESSION_PROGRAM_ID)
+                .payload(game_over(
+                    session_id,
+                    GameStatus::Lose,
+                    None,
+                    &[("house", &[0, 1, 3, 4], &[]); 2],
+                ));
+            assert!(!res.main_failed() && res.contains(&log));
+            let log = Log::builder()
+                .dest(USER)
+                .source(GAME_SESSION_PROGRAM_ID)
+                .payload(GameSessionEvent::AnswerRevealed {
+                    session_id,
+                    answer: "horse".to_string(),
+                });
+            assert!(res.contains(&log));
+        } else {
+            assert!(!res.main_failed());
+        }
+    }
+
+    // 只有管理员可以修改配置
+    let config = GameSessionConfig {
+        tries_limit: 3,
+        timeout_blocks: 100,
+        ..Default::default()
+    };
+    let res = game_session_program.send(51, GameSessionAction::UpdateConfig(config.clone()));
+    assert_error(&res, 51, GameSessionError::NotAdmin);
+
+    // 会话超时不能早于等待回复的超时
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::UpdateConfig(GameSessionConfig {
+            timeout_blocks: 10,
+            reply_timeout_blocks: 10,
+            ..config.clone()
+        }),
+    );
+    assert_error(&res, USER, GameSessionError::InvalidConfig);
+
+    let res = game_session_program.send(USER, GameSessionAction::UpdateConfig(config.clone()));
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::ConfigUpdated);
+    assert!(!res.main_failed() && res.contains(&log));
+
+    let state = full_state(&game_session_program);
+    assert_eq!(state.config, config);
+    assert_eq!(state.admin, ActorId::from(USER));
+
+    // 得分按新会话的尝试次数上限计算
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "human".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let (_, sessions) = archive(&game_session_program, USER, 0, 10);
+    let session = sessions
+        .iter()
+        .find(|session| session.session_id == session_id)
+        .expect("会话未归档");
+    assert!(matches!(session.status, GameStatus::Win));
+    let blocks_taken = session.end_block - session.start_block;
+    assert_eq!(session.score, win_score(1, 3, blocks_taken));
+    assert_eq!(session.score, 300 + 100u32.saturating_sub(blocks_taken));
+}
+
+#[test]
+fn test_rewards() {
+    const UNIT: u128 = 1_000_000_000_000;
+    const PLAYER: u64 = 52;
+
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+    system.mint_to(USER, 100 * UNIT);
+    system.mint_to(PLAYER, 10 * UNIT);
+
+    // 管理员注入资金并设置奖励
+    let res = game_session_program.send_with_value(USER, GameSessionAction::Fund, 10 * UNIT);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Funded { pool: 10 * UNIT });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // 奖励必须随尝试次数递减
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::UpdateRewardConfig(RewardConfig {
+            entry_fee: UNIT,
+            win_rewards: vec![3 * UNIT, 5 * UNIT],
+        }),
+    );
+    assert_error(&res, USER, GameSessionError::InvalidConfig);
+
+    let reward_config = RewardConfig {
+        entry_fee: UNIT,
+        win_rewards: vec![8 * UNIT, 5 * UNIT, 3 * UNIT],
+    };
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::UpdateRewardConfig(reward_config.clone()),
+    );
+    assert_error(&res, PLAYER, GameSessionError::NotAdmin);
+    let res = game_session_program.send(USER, GameSessionAction::UpdateRewardConfig(reward_config));
+    assert!(!res.main_failed());
+
+    // 没有支付入场费（应该失败）
+    let res = game_session_program.send(PLAYER, GameSessionAction::StartGame);
+    assert_error(&res, PLAYER, GameSessionError::WrongEntryFee);
+
+    // 金额不对时随错误退还，不会创建会话
+    let res = game_session_program.send_with_value(PLAYER, GameSessionAction::StartGame, 2 * UNIT);
+    assert_error(&res, PLAYER, GameSessionError::WrongEntryFee);
+    let state = full_state(&game_session_program);
+    assert_eq!(state.pool, 10 * UNIT);
+    assert_eq!(state.session_count, 0);
+
+    let res = game_session_program.send_with_value(PLAYER, GameSessionAction::StartGame, UNIT);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    game_session_program.send(
+        PLAYER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "house".to_string(),
+        },
+    );
+    // 用 2 次猜中，奖励 5 个单位
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RewardPaid { amount: 5 * UNIT });
+    assert!(!res.main_failed() && res.contains(&log));
+    assert_eq!(full_state(&game_session_program).pool, 6 * UNIT);
+
+    // 第二局用 1 次猜中，奖励超过奖池余额，记录下来等待重试
+    let res = game_session_program.send_with_value(PLAYER, GameSessionAction::StartGame, UNIT);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "human".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(
+            session_id,
+            GameStatus::Win,
+            Some("human"),
+            &[("human", &[0, 1, 2, 3, 4], &[])],
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+    let state = full_state(&game_session_program);
+    assert_eq!(state.pool, 7 * UNIT);
+    assert_eq!(
+        state.pending_payouts,
+        vec![(ActorId::from(PLAYER), 8 * UNIT)]
+    );
+
+    let res = game_session_program.send_with_value(USER, GameSessionAction::Fund, 5 * UNIT);
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::RetryPayout {
+            user: PLAYER.into(),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RewardPaid { amount: 8 * UNIT });
+    assert!(!res.main_failed() && res.contains(&log));
+    let state = full_state(&game_session_program);
+    assert_eq!(state.pool, 4 * UNIT);
+    assert!(state.pending_payouts.is_empty());
+
+    // 管理员取回剩余资金
+    let res = game_session_program.send(USER, GameSessionAction::Withdraw { amount: 5 * UNIT });
+    assert_error(&res, USER, GameSessionError::InsufficientPool);
+    let res = game_session_program.send(USER, GameSessionAction::Withdraw { amount: 4 * UNIT });
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Withdrawn { amount: 4 * UNIT });
+    assert!(!res.main_failed() && res.contains(&log));
+}
+
+#[test]
+fn test_ft_rewards() {
+    const FT_PROGRAM_ID: u64 = 4;
+    const PLAYER: u64 = 52;
+
+    let system = System::new();
+    system.init_logger();
+
+    // 部署模拟的代币程序，game_session 持有 10 个代币
+    let balances = Arc::new(Mutex::new(HashMap::new()));
+    balances
+        .lock()
+        .unwrap()
+        .insert(ActorId::from(GAME_SESSION_PROGRAM_ID), 10);
+    let ft_program = Program::mock_with_id(
+        &system,
+        FT_PROGRAM_ID,
+        MockFt {
+            balances: balances.clone(),
+        },
+    );
+    let res = ft_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+
+    let game_session_program = setup_with_ft(&system, Some(FT_PROGRAM_ID));
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::UpdateRewardConfig(RewardConfig {
+            entry_fee: 0,
+            win_rewards: vec![8, 5, 3],
+        }),
+    );
+    assert!(!res.main_failed());
+
+    // 用 2 次猜中，获得 5 个代币
+    let session_id = game_session_program
+        .send(PLAYER, GameSessionAction::StartGame)
+        .sent_message_id();
+    game_session_program.send(
+        PLAYER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "house".to_string(),
+        },
+    );
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RewardPaid { amount: 5 });
+    assert!(!res.main_failed() && res.contains(&log));
+    assert_eq!(balances.lock().unwrap()[&ActorId::from(PLAYER)], 5);
+
+    // 第二局用 1 次猜中，game_session 的代币不足，转账失败后记录下来等待重试
+    let session_id = game_session_program
+        .send(PLAYER, GameSessionAction::StartGame)
+        .sent_message_id();
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "human".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let state = full_state(&game_session_program);
+    assert_eq!(state.pending_payouts, vec![(ActorId::from(PLAYER), 8)]);
+    assert_eq!(state.pool, 0);
+
+    // 补充代币后重试
+    *balances
+        .lock()
+        .unwrap()
+        .get_mut(&ActorId::from(GAME_SESSION_PROGRAM_ID))
+        .unwrap() += 10;
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::RetryPayout {
+            user: PLAYER.into(),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RewardPaid { amount: 8 });
+    assert!(!res.main_failed() && res.contains(&log));
+    assert_eq!(balances.lock().unwrap()[&ActorId::from(PLAYER)], 13);
+    let state = full_state(&game_session_program);
+    assert!(state.pending_payouts.is_empty());
+    assert!(state.pending_rewards.is_empty());
+}
+
+// 桩 Wordle 程序的行为
+#[derive(Debug, Clone, Copy)]
+enum StubMode {
+    // 从不回复
+    Silent,
+    // 回复无法解码的数据
+    Garbage,
+    // 执行失败
+    Failing,
+    // 正常回复，猜测结果总是没有匹配的字母
+    Reply,
+}
+
+#[derive(Debug)]
+struct StubWordle {
+    mode: Arc<Mutex<StubMode>>,
+}
+
+impl WasmProgram for StubWordle {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let action =
+            WordleAction::decode(&mut &payload[..]).map_err(|_| "无法解码 WordleAction")?;
+        match *self.mode.lock().unwrap() {
+            StubMode::Silent => Ok(None),
+            StubMode::Garbage => Ok(Some(vec![0xff; 3])),
+            StubMode::Failing => Err("Wordle 执行失败"),
+            StubMode::Reply => {
+                let event = match action {
+                    WordleAction::StartGame { user }
+                    | WordleAction::StartSharedGame { user, .. }
+                    | WordleAction::StartGameWithWord { user, .. }
+                    | WordleAction::StartSharedGameOf { user, .. } => {
+                        WordleEvent::GameStarted { user }
+                    }
+                    WordleAction::CheckWord { user, .. } => WordleEvent::WordChecked {
+                        user,
+                        correct_positions: vec![],
+                        contained_in_word: vec![],
+                    },
+                    // 不提供答案
+                    WordleAction::Reveal { .. } => return Ok(None),
+                    WordleAction::Forget { .. } | WordleAction::EndGame { .. } => return Ok(None),
+                };
+                Ok(Some(event.encode()))
+            }
+        }
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn test_wordle_unavailable() {
+    let system = System::new();
+    system.init_logger();
+
+    // 部署 game_session 和桩 wordle 程序
+    let mode = Arc::new(Mutex::new(StubMode::Silent));
+    let wordle_program = Program::mock_with_id(
+        &system,
+        WORDLE_PROGRAM_ID,
+        StubWordle { mode: mode.clone() },
+    );
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+
+    let game_session_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(GAME_SESSION_PROGRAM_ID)
+            .build(&system);
+    let config = GameSessionConfig::default();
+    let res = game_session_program.send(
+        USER,
+        GameSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: None,
+            config: config.clone(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    let unavailable = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Error(GameSessionError::WordleUnavailable));
+
+    // Wordle 从不回复，超时后玩家收到 WordleUnavailable
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed() && !res.contains(&unavailable));
+    let results = system.spend_blocks(config.reply_timeout_blocks);
+    assert!(results.iter().any(|res| res.contains(&unavailable)));
+
+    // 没有开始的会话被删除
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "house".to_string(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::NotInGame);
+
+    // 重新开始游戏
+    *mode.lock().unwrap() = StubMode::Reply;
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::StartSuccess { session_id });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // Wordle 回复无法解码的数据或执行失败时不必等到超时，本次猜测不计数
+    for stub_mode in [StubMode::Garbage, StubMode::Failing] {
+        *mode.lock().unwrap() = stub_mode;
+        let res = game_session_program.send(
+            USER,
+            GameSessionAction::CheckWord {
+                session_id,
+                word: "house".to_string(),
+            },
+        );
+        assert!(!res.main_failed() && res.contains(&unavailable));
+    }
+    let anomalies: Vec<_> = full_state(&game_session_program)
+        .anomalies
+        .into_iter()
+        .map(|anomaly| (anomaly.source, anomaly.kind))
+        .collect();
+    assert_eq!(
+        anomalies,
+        vec![
+            (ActorId::from(WORDLE_PROGRAM_ID), AnomalyKind::Undecodable),
+            (ActorId::from(WORDLE_PROGRAM_ID), AnomalyKind::ErrorReply),
+        ]
+    );
+
+    *mode.lock().unwrap() = StubMode::Reply;
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "house".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::CheckWordResult {
+            correct_positions: vec![],
+            contained_in_word: vec![],
+            tries_used: 1,
+            tries_left: 4,
+            keyboard: keyboard(&[("house", &[], &[])]),
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+    let (_, user_sessions) = sessions(&game_session_program, None, 0, 1);
+    assert_eq!(user_sessions[0].1.tries, 1);
+}
+
+#[test]
+fn test_withdraw_during_pending_start() {
+    const UNIT: u128 = 1_000_000_000_000;
+    const PLAYER: u64 = 52;
+
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, 10 * UNIT);
+    system.mint_to(PLAYER, 10 * UNIT);
+
+    // 部署 game_session 和从不回复的桩 wordle 程序
+    let wordle_program = Program::mock_with_id(
+        &system,
+        WORDLE_PROGRAM_ID,
+        StubWordle {
+            mode: Arc::new(Mutex::new(StubMode::Silent)),
+        },
+    );
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+
+    let game_session_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(GAME_SESSION_PROGRAM_ID)
+            .build(&system);
+    let config = GameSessionConfig::default();
+    let res = game_session_program.send(
+        USER,
+        GameSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: None,
+            config: config.clone(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::UpdateRewardConfig(RewardConfig {
+            entry_fee: UNIT,
+            win_rewards: vec![UNIT],
+        }),
+    );
+    assert!(!res.main_failed());
+
+    // 等待 Wordle 回复期间，管理员提取了包括入场费在内的全部资金
+    let res = game_session_program.send_with_value(PLAYER, GameSessionAction::StartGame, UNIT);
+    assert!(!res.main_failed());
+    assert_eq!(full_state(&game_session_program).pool, UNIT);
+    let res = game_session_program.send(USER, GameSessionAction::Withdraw { amount: UNIT });
+    assert!(!res.main_failed());
+
+    // 超时后无法退还入场费，记为待发放
+    let unavailable = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Error(GameSessionError::WordleUnavailable));
+    let results = system.spend_blocks(config.reply_timeout_blocks);
+    assert!(results
+        .iter()
+        .any(|res| !res.main_failed() && res.contains(&unavailable)));
+    let state = full_state(&game_session_program);
+    assert_eq!(state.pool, 0);
+    assert_eq!(state.session_count, 0);
+    assert_eq!(state.pending_payouts, vec![(ActorId::from(PLAYER), UNIT)]);
+
+    // 补充资金后领取
+    let res = game_session_program.send_with_value(USER, GameSessionAction::Fund, UNIT);
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::RetryPayout {
+            user: PLAYER.into(),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RewardPaid { amount: UNIT });
+    assert!(!res.main_failed() && res.contains(&log));
+    let state = full_state(&game_session_program);
+    assert_eq!(state.pool, 0);
+    assert!(state.pending_payouts.is_empty());
+}
+
+// 模拟的 Pebbles 程序：玩家取走石子后，程序每回合取走 1 颗
+#[derive(Debug, Default)]
+struct MockPebbles {
+    pebbles_remaining: u32,
+}
+
+impl WasmProgram for MockPebbles {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let action =
+            PebblesAction::decode(&mut &payload[..]).map_err(|_| "无法解码 PebblesAction")?;
+        let event = match action {
+            PebblesAction::Restart {
+                difficulty,
+                pebbles_count,
+                max_pebbles_per_turn,
+            } => {
+                self.pebbles_remaining = pebbles_count;
+                return Ok(Some(
+                    PebblesInit {
+                        difficulty,
+                        pebbles_count,
+                        max_pebbles_per_turn,
+                    }
+                    .encode(),
+                ));
+            }
+            PebblesAction::Turn(count) => {
+                self.pebbles_remaining = self.pebbles_remaining.saturating_sub(count);
+                if self.pebbles_remaining == 0 {
+                    PebblesEvent::Won(PebblesPlayer::User)
+                } else {
+                    self.pebbles_remaining -= 1;
+                    if self.pebbles_remaining == 0 {
+                        PebblesEvent::Won(PebblesPlayer::Program)
+                    } else {
+                        PebblesEvent::CounterTurn(self.pebbles_remaining)
+                    }
+                }
+            }
+            PebblesAction::GiveUp => PebblesEvent::Won(PebblesPlayer::Program),
+        };
+        Ok(Some(event.encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn test_pebbles_backend() {
+    const PEBBLES_PROGRAM_ID: u64 = 5;
+    const PLAYER: u64 = 52;
+
+    let system = System::new();
+    system.init_logger();
+    let pebbles_program =
+        Program::mock_with_id(&system, PEBBLES_PROGRAM_ID, MockPebbles::default());
+    let res = pebbles_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let game_session_program = setup(&system);
+
+    // 未注册的游戏不能开始
+    let start = GameSessionAction::StartGameWith {
+        program_id: PEBBLES_PROGRAM_ID.into(),
+    };
+    let res = game_session_program.send(PLAYER, start.clone());
+    assert_error(&res, PLAYER, GameSessionError::GameNotRegistered);
+
+    // 只有管理员可以注册游戏
+    let game = GameKind::Pebbles(PebblesInit {
+        difficulty: DifficultyLevel::Easy,
+        pebbles_count: 5,
+        max_pebbles_per_turn: 2,
+    });
+    let register = GameSessionAction::RegisterGame {
+        program_id: PEBBLES_PROGRAM_ID.into(),
+        game,
+    };
+    let res = game_session_program.send(PLAYER, register.clone());
+    assert_error(&res, PLAYER, GameSessionError::NotAdmin);
+    let res = game_session_program.send(USER, register);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::GameRegistered {
+            program_id: PEBBLES_PROGRAM_ID.into(),
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+    let state = full_state(&game_session_program);
+    assert!(state
+        .games
+        .contains(&(ActorId::from(PEBBLES_PROGRAM_ID), game)));
+
+    let res = game_session_program.send(PLAYER, start.clone());
+    let session_id = res.sent_message_id();
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::StartSuccess { session_id });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // Pebbles 程序同一时间只能进行一局
+    let res = game_session_program.send(USER, start);
+    assert_error(&res, USER, GameSessionError::GameBusy);
+
+    // 单词不是 Pebbles 的操作
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "house".to_string(),
+        },
+    );
+    assert_error(&res, PLAYER, GameSessionError::UnsupportedInput);
+
+    // 5 - 2 - 1 = 2
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::Play {
+            session_id,
+            input: GameInput::Pebbles(2),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::CounterTurn {
+            pebbles_remaining: 2,
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // 取走最后的石子获胜
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::Play {
+            session_id,
+            input: GameInput::Pebbles(2),
+        },
+    );
+    let log = Log::builder()
+        .dest(PLAYER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(session_id, GameStatus::Win, None, &[]));
+    assert!(!res.main_failed() && res.contains(&log));
+    let (total, sessions) = archive(&game_session_program, PLAYER, 0, 10);
+    assert_eq!(total, 1);
+    assert_eq!(sessions[0].tries, 2);
+    assert!(sessions[0].guesses.is_empty());
+    // Pebbles 的会话不计入 Wordle 的统计数据
+    let StateReply::PlayerStats(stats) = game_session_program
+        .read_state(StateQuery::PlayerStats(PLAYER.into()))
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    assert_eq!(stats, None);
+
+    // 移除后不能再开始新的一局，默认的 Wordle 程序不能移除
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::UnregisterGame {
+            program_id: WORDLE_PROGRAM_ID.into(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::CannotUnregisterWordle);
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::UnregisterGame {
+            program_id: PEBBLES_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        PLAYER,
+        GameSessionAction::StartGameWith {
+            program_id: PEBBLES_PROGRAM_ID.into(),
+        },
+    );
+    assert_error(&res, PLAYER, GameSessionError::GameNotRegistered);
+}
+
+#[test]
+fn test_abandon_and_restart() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+
+    // 只能放弃自己的会话
+    let res = game_session_program.send(51, GameSessionAction::Abandon { session_id });
+    assert_error(&res, 51, GameSessionError::NotInGame);
+
+    let res = game_session_program.send(USER, GameSessionAction::Abandon { session_id });
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(session_id, GameStatus::Lose, None, &[]));
+    assert!(!res.main_failed() && res.contains(&log));
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::AnswerRevealed {
+            session_id,
+            answer: "horse".to_string(),
+        });
+    assert!(res.contains(&log));
+
+    // 放弃后不能继续猜测，也不能再次放弃或重新开始
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::NotInGame);
+    let res = game_session_program.send(USER, GameSessionAction::Restart { session_id });
+    assert_error(&res, USER, GameSessionError::NotInGame);
+
+    // 猜测一次后重新开始，旧会话以失败归档
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "house".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = game_session_program.send(USER, GameSessionAction::Restart { session_id });
+    let new_session_id = res.sent_message_id();
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::StartSuccess {
+            session_id: new_session_id,
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    let (total, sessions) = archive(&game_session_program, USER, 0, 10);
+    assert_eq!(total, 2);
+    assert_eq!(sessions[1].session_id, session_id);
+    assert_eq!(sessions[1].tries, 1);
+    assert!(matches!(sessions[1].status, GameStatus::Lose));
+    assert_eq!(sessions[1].answer.as_deref(), Some("human"));
+    let StateReply::PlayerStats(Some(stats)) = game_session_program
+        .read_state(StateQuery::PlayerStats(USER.into()))
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    assert_eq!((stats.wins, stats.losses), (0, 2));
+
+    // 新会话可以正常进行，第三局的单词为 "house"
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id: new_session_id,
+            word: "house".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(
+            new_session_id,
+            GameStatus::Win,
+            Some("house"),
+            &[("house", &[0, 1, 2, 3, 4], &[])],
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+}
+
+#[test]
+fn test_race() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+    let race_id = 0;
+
+    let res = game_session_program.send(51, GameSessionAction::JoinRace { race_id });
+    assert_error(&res, 51, GameSessionError::RaceNotFound);
+
+    // 创建者自动加入房间
+    let res = game_session_program.send(USER, GameSessionAction::CreateRace);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RaceCreated { race_id });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    let res = game_session_program.send(51, GameSessionAction::JoinRace { race_id });
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RaceJoined {
+            race_id,
+            players: 2,
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+    let res = game_session_program.send(51, GameSessionAction::JoinRace { race_id });
+    assert_error(&res, 51, GameSessionError::AlreadyInRace);
+
+    // 只有创建者可以开始，开始之前不能开始游戏
+    let res = game_session_program.send(51, GameSessionAction::StartRace { race_id });
+    assert_error(&res, 51, GameSessionError::NotRaceHost);
+    let res = game_session_program.send(51, GameSessionAction::StartRaceGame { race_id });
+    assert_error(&res, 51, GameSessionError::RaceNotRunning);
+
+    let res = game_session_program.send(USER, GameSessionAction::StartRace { race_id });
+    assert!(!res.main_failed());
+    for player in [USER, 51] {
+        let log = Log::builder()
+            .dest(player)
+            .source(GAME_SESSION_PROGRAM_ID)
+            .payload(GameSessionEvent::RaceStarted { race_id });
+        assert!(res.contains(&log));
+    }
+
+    // 开始后不能再加入
+    let res = game_session_program.send(52, GameSessionAction::JoinRace { race_id });
+    assert_error(&res, 52, GameSessionError::RaceNotOpen);
+    let res = game_session_program.send(52, GameSessionAction::StartRaceGame { race_id });
+    assert_error(&res, 52, GameSessionError::NotInRace);
+
+    let res = game_session_program.send(USER, GameSessionAction::StartRaceGame { race_id });
+    let session_id = res.sent_message_id();
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::StartSuccess { session_id });
+    assert!(!res.main_failed() && res.contains(&log));
+    let res = game_session_program.send(USER, GameSessionAction::StartRaceGame { race_id });
+    assert_error(&res, USER, GameSessionError::AlreadyInGame);
+
+    let res = game_session_program.send(51, GameSessionAction::StartRaceGame { race_id });
+    let other_session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+
+    // 两个玩家猜同一个单词
+    let res = game_session_program.send(
+        51,
+        GameSessionAction::CheckWord {
+            session_id: other_session_id,
+            word: "house".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::CheckWordResult {
+            correct_positions: vec![0, 1, 3, 4],
+            contained_in_word: vec![],
+            tries_used: 1,
+            tries_left: 4,
+            keyboard: keyboard(&[("house", &[0, 1, 3, 4], &[])]),
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // 先猜中的玩家获胜，其他玩家的会话以失败结束
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(
+            session_id,
+            GameStatus::Win,
+            Some("horse"),
+            &[("horse", &[0, 1, 2, 3, 4], &[])],
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(
+            other_session_id,
+            GameStatus::Lose,
+            None,
+            &[("house", &[0, 1, 3, 4], &[])],
+        ));
+    assert!(res.contains(&log));
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RaceFinished {
+            race_id,
+            winner: USER.into(),
+        });
+    assert!(res.contains(&log));
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::AnswerRevealed {
+            session_id: other_session_id,
+            answer: "horse".to_string(),
+        });
+    assert!(res.contains(&log));
+
+    let res = game_session_program.send(
+        51,
+        GameSessionAction::CheckWord {
+            session_id: other_session_id,
+            word: "horse".to_string(),
+        },
+    );
+    assert_error(&res, 51, GameSessionError::NotInGame);
+
+    let state = full_state(&game_session_program);
+    let (_, race) = &state.races[0];
+    assert_eq!(race.players, vec![USER.into(), ActorId::from(51)]);
+    assert_eq!(
+        race.status,
+        RaceStatus::Finished {
+            winner: USER.into()
+        }
+    );
+}
+
+#[test]
+fn test_race_answer_hidden() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+    let wordle_program = system.get_program(WORDLE_PROGRAM_ID);
+    let race_id = 0;
+
+    let res = game_session_program.send(USER, GameSessionAction::CreateRace);
+    assert!(!res.main_failed());
+    for player in [51, 52] {
+        let res = game_session_program.send(player, GameSessionAction::JoinRace { race_id });
+        assert!(!res.main_failed());
+    }
+    let res = game_session_program.send(USER, GameSessionAction::StartRace { race_id });
+    assert!(!res.main_failed());
+    let mut session_ids = HashMap::new();
+    for player in [USER, 51, 52] {
+        let res = game_session_program.send(player, GameSessionAction::StartRaceGame { race_id });
+        assert!(!res.main_failed());
+        session_ids.insert(player, res.sent_message_id());
+    }
+    let revealed = |player: u64| {
+        Log::builder()
+            .dest(player)
+            .source(GAME_SESSION_PROGRAM_ID)
+            .payload(GameSessionEvent::AnswerRevealed {
+                session_id: session_ids[&player],
+                answer: "horse".to_string(),
+            })
+    };
+    let wordle_word = |player: u64| {
+        let WordleStateReply::GuessHistory(info) = wordle_program
+            .read_state(WordleStateQuery::GuessHistory(wordle_player(
+                session_ids[&player],
+            )))
+            .unwrap()
+        else {
+            panic!("状态查询回复错误");
+        };
+        info.expect("Wordle 中没有该会话的游戏").word
+    };
+
+    // 竞速进行中放弃的玩家拿不到答案，Wordle 的状态中也查不到
+    let res = game_session_program.send(
+        52,
+        GameSessionAction::Abandon {
+            session_id: session_ids[&52],
+        },
+    );
+    let log = Log::builder()
+        .dest(52)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(session_ids[&52], GameStatus::Lose, None, &[]));
+    assert!(!res.main_failed() && res.contains(&log));
+    assert!(!res.contains(&revealed(52)));
+    assert_eq!(wordle_word(52), None);
+
+    // 用完尝试次数的玩家也一样
+    for tries_used in 1..=WORDLE_MAX_ATTEMPTS {
+        let res = game_session_program.send(
+            51,
+            GameSessionAction::CheckWord {
+                session_id: session_ids[&51],
+                word: "human".to_string(),
+            },
+        );
+        assert!(!res.main_failed());
+        if tries_used == WORDLE_MAX_ATTEMPTS {
+            let guesses = [("human", &[0][..], &[][..]); WORDLE_MAX_ATTEMPTS as usize];
+            let log = Log::builder()
+                .dest(51)
+                .source(GAME_SESSION_PROGRAM_ID)
+                .payload(game_over(
+                    session_ids[&51],
+                    GameStatus::Lose,
+                    None,
+                    &guesses,
+                ));
+            assert!(res.contains(&log));
+            assert!(!res.contains(&revealed(51)));
+        }
+    }
+    assert_eq!(wordle_word(51), None);
+    let (_, records) = archive(&game_session_program, 51, 0, 1);
+    assert_eq!(records[0].answer, None);
+
+    // 竞速结束后所有玩家都收到答案
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id: session_ids[&USER],
+            word: "horse".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    assert!(res.contains(&revealed(51)) && res.contains(&revealed(52)));
+    let (_, records) = archive(&game_session_program, 52, 0, 1);
+    assert_eq!(records[0].answer.as_deref(), Some("horse"));
+}
+
+#[test]
+fn test_challenge() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::Challenge {
+            opponent: USER.into(),
+            word: "human".to_string(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::InvalidOpponent);
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::Challenge {
+            opponent: 51.into(),
+            word: "Human".to_string(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::InvalidWord);
+
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::Challenge {
+            opponent: 51.into(),
+            word: "human".to_string(),
+        },
+    );
+    let challenge_id = res.sent_message_id();
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::ChallengeSent { challenge_id });
+    assert!(!res.main_failed() && res.contains(&log));
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::ChallengeReceived {
+            challenge_id,
+            challenger: USER.into(),
+        });
+    assert!(res.contains(&log));
+
+    // 只有被挑战的玩家可以接受，且只能接受一次
+    let res = game_session_program.send(52, GameSessionAction::AcceptChallenge { challenge_id });
+    assert_error(&res, 52, GameSessionError::ChallengeNotFound);
+    let res = game_session_program.send(51, GameSessionAction::AcceptChallenge { challenge_id });
+    let session_id = res.sent_message_id();
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::StartSuccess { session_id });
+    assert!(!res.main_failed() && res.contains(&log));
+    let res = game_session_program.send(51, GameSessionAction::AcceptChallenge { challenge_id });
+    assert_error(&res, 51, GameSessionError::ChallengeNotFound);
+
+    // 对手猜的是挑战者选择的单词，结果发送给挑战者
+    let res = game_session_program.send(
+        51,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "human".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(51)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(
+            session_id,
+            GameStatus::Win,
+            Some("human"),
+            &[("human", &[0, 1, 2, 3, 4], &[])],
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::ChallengeFinished {
+            challenge_id,
+            opponent: 51.into(),
+            status: GameStatus::Win,
+        });
+    assert!(res.contains(&log));
+    assert!(full_state(&game_session_program).challenges.is_empty());
+
+    // 不在词典中的单词在创建挑战时被拒绝
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::Challenge {
+            opponent: 51.into(),
+            word: "abcde".to_string(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::WordNotInDictionary);
+    assert!(full_state(&game_session_program).challenges.is_empty());
+
+    // 对手可以拒绝挑战
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::Challenge {
+            opponent: 51.into(),
+            word: "house".to_string(),
+        },
+    );
+    let challenge_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    assert_eq!(
+        full_state(&game_session_program).challenges,
+        vec![(challenge_id, USER.into(), 51.into())]
+    );
+
+    let res = game_session_program.send(51, GameSessionAction::DeclineChallenge { challenge_id });
+    for player in [USER, 51] {
+        let log = Log::builder()
+            .dest(player)
+            .source(GAME_SESSION_PROGRAM_ID)
+            .payload(GameSessionEvent::ChallengeDeclined { challenge_id });
+        assert!(!res.main_failed() && res.contains(&log));
+    }
+    let res = game_session_program.send(51, GameSessionAction::DeclineChallenge { challenge_id });
+    assert_error(&res, 51, GameSessionError::ChallengeNotFound);
+}
+
+#[test]
+fn test_prune() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+
+    let config = GameSessionConfig {
+        retention_blocks: 10,
+        ..Default::default()
+    };
+    let res = game_session_program.send(USER, GameSessionAction::UpdateConfig(config));
+    assert!(!res.main_failed());
+
+    // 两局结束，一局进行中
+    let mut session_ids = Vec::new();
+    for _ in 0..3 {
+        let res = game_session_program.send(USER, GameSessionAction::StartGame);
+        assert!(!res.main_failed());
+        session_ids.push(res.sent_message_id());
+    }
+    for session_id in &session_ids[..2] {
+        let res = game_session_program.send(
+            USER,
+            GameSessionAction::Abandon {
+                session_id: *session_id,
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    // 保留期内不清理
+    let res = game_session_program.send(USER, GameSessionAction::Prune { limit: 10 });
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Pruned {
+            sessions: 0,
+            races: 0,
+            challenges: 0,
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    system.spend_blocks(10);
+    let res = game_session_program.send(51, GameSessionAction::Prune { limit: 10 });
+    assert_error(&res, 51, GameSessionError::NotAdmin);
+    let res = game_session_program.send(USER, GameSessionAction::Prune { limit: 1 });
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Pruned {
+            sessions: 1,
+            races: 0,
+            challenges: 0,
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+    assert_eq!(full_state(&game_session_program).session_count, 2);
+
+    // 开始新的一局时清理该玩家其余过期的会话，进行中的会话保留
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let new_session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let (total, user_sessions) = sessions(&game_session_program, Some(USER), 0, 10);
+    assert_eq!(total, 2);
+    let mut ids: Vec<_> = user_sessions.iter().map(|(_, s)| s.session_id).collect();
+    ids.sort();
+    let mut expected = vec![session_ids[2], new_session_id];
+    expected.sort();
+    assert_eq!(ids, expected);
+
+    // 归档记录和统计数据不受影响
+    assert_eq!(archive(&game_session_program, USER, 0, 10).0, 2);
+}
+
+#[test]
+fn test_prune_races_and_challenges() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+    let wordle_program = system.get_program(WORDLE_PROGRAM_ID);
+
+    let config = GameSessionConfig {
+        retention_blocks: 10,
+        ..Default::default()
+    };
+    let res = game_session_program.send(USER, GameSessionAction::UpdateConfig(config));
+    assert!(!res.main_failed());
+
+    // 每个玩家同时只能有一个等待玩家加入的竞速
+    let res = game_session_program.send(USER, GameSessionAction::CreateRace);
+    assert!(!res.main_failed());
+    let res = game_session_program.send(USER, GameSessionAction::CreateRace);
+    assert_error(&res, USER, GameSessionError::RaceAlreadyOpen);
+
+    // 竞速结束后在 Wordle 中留下玩家结束的游戏和共用的单词
+    let res = game_session_program.send(USER, GameSessionAction::StartRace { race_id: 0 });
+    assert!(!res.main_failed());
+    let res = game_session_program.send(USER, GameSessionAction::StartRaceGame { race_id: 0 });
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let guess_history = || {
+        let WordleStateReply::GuessHistory(info) = wordle_program
+            .read_state(WordleStateQuery::GuessHistory(USER.into()))
+            .unwrap()
+        else {
+            panic!("状态查询回复错误");
+        };
+        info
+    };
+    assert!(guess_history().is_some());
+
+    // 未被接受的挑战有数量上限
+    for _ in 0..MAX_PENDING_CHALLENGES {
+        let res = game_session_program.send(
+            USER,
+            GameSessionAction::Challenge {
+                opponent: 51.into(),
+                word: "house".to_string(),
+            },
+        );
+        assert!(!res.main_failed());
+    }
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::Challenge {
+            opponent: 51.into(),
+            word: "house".to_string(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::TooManyChallenges);
+
+    // 超过保留期后清理会话、竞速和挑战，Wordle 中的记录一并删除
+    system.spend_blocks(10);
+    let res = game_session_program.send(USER, GameSessionAction::Prune { limit: 100 });
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Pruned {
+            sessions: 1,
+            races: 1,
+            challenges: MAX_PENDING_CHALLENGES as u32,
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+    let state = full_state(&game_session_program);
+    assert!(state.races.is_empty());
+    assert!(state.challenges.is_empty());
+    assert!(guess_history().is_none());
+
+    // 竞速的编号不会重复使用
+    let res = game_session_program.send(USER, GameSessionAction::CreateRace);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::RaceCreated { race_id: 1 });
+    assert!(!res.main_failed() && res.contains(&log));
+}
+
+#[test]
+fn test_gas_reservations() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+
+    let res = game_session_program.send(51, GameSessionAction::ReserveGas { count: 2 });
+    assert_error(&res, 51, GameSessionError::NotAdmin);
+    let res = game_session_program.send(USER, GameSessionAction::ReserveGas { count: 2 });
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::GasReserved { available: 2 });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // 超时检查使用预留的 gas
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let reservations = full_state(&game_session_program).reservations;
+    assert_eq!(reservations.len(), 1);
+    assert_eq!(
+        reservations[0].amount,
+        GameSessionConfig::default().check_status_gas
+    );
+
+    let result = system.spend_blocks(GameSessionConfig::default().timeout_blocks);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(session_id, GameStatus::Lose, None, &[]));
+    assert!(result.iter().any(|res| res.contains(&log)));
+
+    let res = game_session_program.send(USER, GameSessionAction::UnreserveGas { count: 5 });
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::GasUnreserved { available: 0 });
+    assert!(!res.main_failed() && res.contains(&log));
+    assert!(full_state(&game_session_program).reservations.is_empty());
+
+    // 没有预留时仍使用玩家消息的 gas
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let result = system.spend_blocks(GameSessionConfig::default().timeout_blocks);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(game_over(session_id, GameStatus::Lose, None, &[]));
+    assert!(result.iter().any(|res| res.contains(&log)));
+}
+
+#[test]
+fn test_pause_and_migrate() {
+    let system = System::new();
+    system.init_logger();
+    let game_session_program = setup(&system);
+    let wordle_program = system.get_program(WORDLE_PROGRAM_ID);
+
+    // 第一局的单词为 "horse"，第二局为 "human"，竞速为 "house"
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    let won_session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id: won_session_id,
+            word: "human".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = game_session_program.send(USER, GameSessionAction::CreateRace);
+    assert!(!res.main_failed());
+    let res = game_session_program.send(51, GameSessionAction::JoinRace { race_id: 0 });
+    assert!(!res.main_failed());
+    let res = game_session_program.send(USER, GameSessionAction::StartRace { race_id: 0 });
+    assert!(!res.main_failed());
+    let res = game_session_program.send(USER, GameSessionAction::StartRaceGame { race_id: 0 });
+    let race_session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+
+    let res = game_session_program.send(51, GameSessionAction::Pause);
+    assert_error(&res, 51, GameSessionError::NotAdmin);
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::ExportState {
+            offset: 0,
+            limit: 2,
+        },
+    );
+    assert_error(&res, USER, GameSessionError::NotPaused);
+
+    let res = game_session_program.send(USER, GameSessionAction::Pause);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAME_SESSION_PROGRAM_ID)
+        .payload(GameSessionEvent::Paused);
+    assert!(!res.main_failed() && res.contains(&log));
+    assert!(full_state(&game_session_program).paused);
+
+    // 暂停时不能开始新的会话，进行中的会话不受影响
+    let res = game_session_program.send(USER, GameSessionAction::StartGame);
+    assert_error(&res, USER, GameSessionError::Paused);
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "house".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // 状态分页导出，每页的各个列表最多 2 项
+    let export_page = |offset| {
+        let res =
+            game_session_program.send(USER, GameSessionAction::ExportState { offset, limit: 2 });
+        assert!(!res.main_failed());
+        res.log()
+            .iter()
+            .find_map(|log| match GameSessionEvent::decode(&mut log.payload()) {
+                Ok(GameSessionEvent::StateExported { total, snapshot }) => Some((total, snapshot)),
+                _ => None,
+            })
+            .expect("没有导出的状态")
+    };
+    let (total, snapshot) = export_page(0);
+    assert_eq!(total, 3);
+    assert_eq!(snapshot.version, STATE_VERSION);
+    assert_eq!(snapshot.program_id, ActorId::from(GAME_SESSION_PROGRAM_ID));
+    assert_eq!(snapshot.sessions.len(), 2);
+    let (_, last_page) = export_page(2);
+    assert_eq!(last_page.sessions.len(), 1);
+    assert!(last_page.races.is_empty());
+    let (_, repeated) = export_page(0);
+    assert_eq!(repeated.encode(), snapshot.encode());
+
+    // 导出后旧程序不再接受会话的动作，也不能恢复
+    assert!(full_state(&game_session_program).migrated);
+    let res = game_session_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    assert_error(&res, USER, GameSessionError::Migrated);
+    let res = game_session_program.send(51, GameSessionAction::StartRaceGame { race_id: 0 });
+    assert_error(&res, 51, GameSessionError::Migrated);
+    let res = game_session_program.send(USER, GameSessionAction::Resume);
+    assert_error(&res, USER, GameSessionError::Migrated);
+
+    // 部署新版本程序并导入状态，新程序需要成为 Wordle 的可信程序
+    let new_program_id = 3;
+    let new_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(new_program_id)
+            .build(&system);
+    let res = new_program.send(
+        USER,
+        GameSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: Some(GameSessionSnapshot {
+                version: STATE_VERSION + 1,
+                ..snapshot.clone()
+            }),
+            config: GameSessionConfig::default(),
+        },
+    );
+    assert!(res.main_failed());
+
+    let new_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/game_session.opt.wasm")
+            .with_id(new_program_id + 1)
+            .build(&system);
+    let res = new_program.send(
+        USER,
+        GameSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ft_program_id: None,
+            import: Some(snapshot),
+            config: GameSessionConfig::default(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program: (new_program_id + 1).into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // 导入完成前不能继续会话
+    let state = full_state(&new_program);
+    assert!(state.paused && state.importing);
+    let res = new_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(new_program_id + 1)
+        .payload(GameSessionEvent::Error(GameSessionError::Importing));
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // 其余各页必须来自同一个程序
+    let res = new_program.send(
+        USER,
+        GameSessionAction::ImportState(GameSessionSnapshot {
+            program_id: new_program_id.into(),
+            ..last_page.clone()
+        }),
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(new_program_id + 1)
+        .payload(GameSessionEvent::Error(GameSessionError::InvalidSnapshot));
+    assert!(!res.main_failed() && res.contains(&log));
+    let res = new_program.send(USER, GameSessionAction::ImportState(last_page));
+    let log = Log::builder()
+        .dest(USER)
+        .source(new_program_id + 1)
+        .payload(GameSessionEvent::StateImported);
+    assert!(!res.main_failed() && res.contains(&log));
+    let res = new_program.send(USER, GameSessionAction::Resume);
+    assert!(!res.main_failed());
+
+    let state = full_state(&new_program);
+    assert!(!state.paused && !state.importing);
+    assert_eq!(state.session_count, 3);
+    assert_eq!(state.unscheduled_checks, 2);
+    assert_eq!(archive(&new_program, USER, 0, 10).0, 1);
+
+    // 在新程序中继续进行中的会话，同时安排超时检查
+    let res = new_program.send(
+        USER,
+        GameSessionAction::CheckWord {
+            session_id,
+            word: "horse".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(new_program_id + 1)
+        .payload(game_over(
+            session_id,
+            GameStatus::Win,
+            Some("horse"),
+            &[
+                ("house", &[0, 1, 3, 4], &[]),
+                ("horse", &[0, 1, 2, 3, 4], &[]),
+            ],
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+    let StateReply::PlayerStats(Some(stats)) = new_program
+        .read_state(StateQuery::PlayerStats(USER.into()))
+        .unwrap()
+    else {
+        panic!("状态查询回复错误");
+    };
+    assert_eq!(stats.wins, 2);
+    assert_eq!(full_state(&new_program).unscheduled_checks, 1);
+
+    // 管理员为其余的会话安排超时检查，保持原来的到期区块
+    let res = new_program.send(51, GameSessionAction::ScheduleChecks { limit: 10 });
+    let log = Log::builder()
+        .dest(51)
+        .source(new_program_id + 1)
+        .payload(GameSessionEvent::Error(GameSessionError::NotAdmin));
+    assert!(!res.main_failed() && res.contains(&log));
+    let res = new_program.send(USER, GameSessionAction::ScheduleChecks { limit: 10 });
+    let log = Log::builder()
+        .dest(USER)
+        .source(new_program_id + 1)
+        .payload(GameSessionEvent::ChecksScheduled { remaining: 0 });
+    assert!(!res.main_failed() && res.contains(&log));
+    let results = system.spend_blocks(GameSessionConfig::default().timeout_blocks);
+    let log = Log::builder()
+        .dest(USER)
+        .source(new_program_id + 1)
+        .payload(game_over(race_session_id, GameStatus::Lose, None, &[]));
+    assert!(results.iter().any(|res| res.contains(&log)));
+    // 旧程序安排的检查被忽略
+    let log = Log::builder().dest(USER).source(GAME_SESSION_PROGRAM_ID);
+    assert!(!results.iter().any(|res| res.contains(&log)));
+
+    // 进行中的竞速继续使用旧程序在 Wordle 中的单词，而不是重新抽取的 "horse"
+    let res = new_program.send(51, GameSessionAction::StartRaceGame { race_id: 0 });
+    let race_session_id = res.sent_message_id();
+    assert!(!res.main_failed());
+    let res = new_program.send(
+        51,
+        GameSessionAction::CheckWord {
+            session_id: race_session_id,
+            word: "house".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(51)
+        .source(new_program_id + 1)
+        .payload(game_over(
+            race_session_id,
+            GameStatus::Win,
+            Some("house"),
+            &[("house", &[0, 1, 2, 3, 4], &[])],
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+}
@@ -2,4 +2,4 @@ use wordle_io::WordleMetadata;
 
 fn main() {
     gear_wasm_builder::build_with_metadata::<WordleMetadata>();
-}
\ No newline at end of file
+}
@@ -1,17 +1,34 @@
 #![no_std]
 
-use gmeta::{InOut, Metadata};
+use gmeta::{In, InOut, Metadata};
 use gstd::{prelude::*, ActorId};
 
 pub struct WordleMetadata;
 
 impl Metadata for WordleMetadata {
-    type Init = ();
+    type Init = In<WordleInit>;
     type Handle = InOut<Action, Event>;
     type Others = ();
     type Reply = ();
     type Signal = ();
-    type State = ();
+    type State = InOut<StateQuery, StateReply>;
+}
+
+/// The words the answers are drawn from. Custom answers, such as the word of a
+/// challenge, must be taken from it as well.
+pub const BANK_OF_WORDS: [&str; 3] = ["house", "human", "horse"];
+
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
+pub struct WordleInit {
+    /// Draws the words from a fixed seed instead of the runtime randomness,
+    /// so that tests can predict the answers. Only accepted by builds with
+    /// the `test-rng` feature, other builds refuse to initialize with a seed.
+    pub seed: Option<u64>,
+    /// Number of guesses after which an unsolved game is lost.
+    pub max_attempts: u8,
+    /// Programs allowed to act for any user, such as a game session proxy.
+    /// Other callers may only act for themselves.
+    pub trusted_programs: Vec<ActorId>,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
@@ -22,10 +39,58 @@ pub enum Action {
     CheckWord {
         user: ActorId,
         word: String,
-    }
+    },
+    /// Starts the shared puzzle of the current period. Every user gets the
+    /// same answer within a period and may play it only once.
+    StartDailyGame {
+        user: ActorId,
+    },
+    /// Admin only.
+    AddTrustedProgram {
+        program: ActorId,
+    },
+    /// Admin only.
+    RemoveTrustedProgram {
+        program: ActorId,
+    },
+    /// Replies with the answer of the user's last finished game. A game in
+    /// progress has to be ended first, see `EndGame`.
+    Reveal {
+        user: ActorId,
+    },
+    /// Starts a game whose answer is shared by every user started with the
+    /// same `shared_id` by the same caller, e.g. the players of a race.
+    StartSharedGame {
+        user: ActorId,
+        shared_id: u64,
+    },
+    /// Trusted programs only. Starts a game with a word chosen by someone
+    /// else, e.g. a challenger. The word must be in the dictionary.
+    StartGameWithWord {
+        user: ActorId,
+        word: String,
+    },
+    /// Deletes the last finished game of each user and the answers of the
+    /// caller's shared games, once a proxy no longer needs them. Games in
+    /// progress are kept.
+    Forget {
+        users: Vec<ActorId>,
+        shared_ids: Vec<u64>,
+    },
+    /// Trusted programs only. Starts a shared game of another caller `owner`,
+    /// e.g. a race carried over from a proxy that has been replaced.
+    StartSharedGameOf {
+        user: ActorId,
+        owner: ActorId,
+        shared_id: u64,
+    },
+    /// Trusted programs only. Ends the user's game in progress as lost, e.g.
+    /// when the proxy's session has ended. The answer is not revealed.
+    EndGame {
+        user: ActorId,
+    },
 }
 
-
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum Event {
     GameStarted {
@@ -36,4 +101,98 @@ pub enum Event {
         correct_positions: Vec<u8>,
         contained_in_word: Vec<u8>,
     },
-}
\ No newline at end of file
+    /// Reply to the guess that solved the game.
+    GameWon {
+        user: ActorId,
+    },
+    /// Reply to the last allowed guess when it did not solve the game.
+    GameLost {
+        user: ActorId,
+        correct_positions: Vec<u8>,
+        contained_in_word: Vec<u8>,
+        answer: String,
+    },
+    TrustedProgramAdded {
+        program: ActorId,
+    },
+    TrustedProgramRemoved {
+        program: ActorId,
+    },
+    Revealed {
+        user: ActorId,
+        answer: String,
+    },
+    Forgotten,
+    GameEnded {
+        user: ActorId,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum StateQuery {
+    Daily,
+    /// Users who have a game in progress.
+    Players,
+    /// Number of games in progress.
+    GameCount,
+    DictionarySize,
+    /// The game in progress of a user, or the last finished one.
+    GuessHistory(ActorId),
+    TrustedPrograms,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum StateReply {
+    Daily(DailyInfo),
+    Players(Vec<ActorId>),
+    GameCount(u32),
+    DictionarySize(u32),
+    GuessHistory(Option<GameInfo>),
+    TrustedPrograms(Vec<ActorId>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum GameStatus {
+    InProgress,
+    Won,
+    Lost,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Guess {
+    pub word: String,
+    pub correct_positions: Vec<u8>,
+    pub contained_in_word: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct GameInfo {
+    /// The secret word, `None` until the game is over. The word of a shared
+    /// game is only given by `Reveal`, other users may still be playing it.
+    pub word: Option<String>,
+    pub daily_period: Option<u64>,
+    pub status: GameStatus,
+    pub guesses: Vec<Guess>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct DailyStats {
+    pub players: u32,
+    pub solved: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct DailyResult {
+    pub period: u64,
+    pub answer: String,
+    pub stats: DailyStats,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct DailyInfo {
+    /// Index of the current period, `block_timestamp / period length`.
+    pub period: u64,
+    pub stats: DailyStats,
+    /// The previous period, whose answer may now be revealed.
+    pub previous: Option<DailyResult>,
+}
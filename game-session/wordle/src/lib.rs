@@ -1,23 +1,243 @@
 #![no_std]
-use gstd::{collections::HashMap, exec, msg, prelude::*, ActorId};
+use gstd::{
+    collections::{BTreeMap, HashMap, HashSet},
+    exec, msg,
+    prelude::*,
+    ActorId,
+};
 use wordle_io::*;
 
 static mut WORDLE: Option<Wordle> = None;
 
-const BANK_OF_WORDS: [&str; 3] = ["house", "human", "horse"];
+/// Length of a daily puzzle period in milliseconds.
+const DAILY_PERIOD_MS: u64 = 24 * 60 * 60 * 1000;
+
+struct Game {
+    word: String,
+    /// The period of the shared puzzle, `None` for a random game.
+    daily_period: Option<u64>,
+    /// Whether the word is shared with the games of other users.
+    shared: bool,
+    status: GameStatus,
+    guesses: Vec<Guess>,
+}
+
+impl Game {
+    fn new(word: String, daily_period: Option<u64>) -> Self {
+        Self {
+            word,
+            daily_period,
+            shared: false,
+            status: GameStatus::InProgress,
+            guesses: Vec::new(),
+        }
+    }
+
+    fn new_shared(word: String) -> Self {
+        Self {
+            shared: true,
+            ..Self::new(word, None)
+        }
+    }
+
+    fn check(&mut self, word: String) -> Guess {
+        let key_word = &self.word;
+        let mut matched_indices = Vec::with_capacity(5);
+        let mut key_indices = Vec::with_capacity(5);
+        for (i, (a, b)) in key_word.chars().zip(word.chars()).enumerate() {
+            if a == b {
+                matched_indices.push(i as u8);
+            } else if key_word.contains(b) {
+                key_indices.push(i as u8);
+            }
+        }
+
+        let guess = Guess {
+            word,
+            correct_positions: matched_indices,
+            contained_in_word: key_indices,
+        };
+        self.guesses.push(guess.clone());
+        guess
+    }
+
+    fn info(&self) -> GameInfo {
+        GameInfo {
+            word: (self.status != GameStatus::InProgress && !self.shared)
+                .then(|| self.word.clone()),
+            daily_period: self.daily_period,
+            status: self.status,
+            guesses: self.guesses.clone(),
+        }
+    }
+}
+
+/// Source of random word indices.
+enum Rng {
+    /// Salts `exec::random` with the incoming message, the user and a nonce.
+    Runtime { nonce: u64 },
+    /// A SplitMix64 sequence, fully determined by the seed.
+    Seeded { state: u64 },
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::Runtime { nonce: 0 }
+    }
+}
+
+impl Rng {
+    fn next_u64(&mut self, user: ActorId) -> u64 {
+        match self {
+            Self::Runtime { nonce } => {
+                let mut subject: [u8; 32] = msg::id().into();
+                let user: [u8; 32] = user.into();
+                for (s, u) in subject.iter_mut().zip(user) {
+                    *s ^= u;
+                }
+                for (s, n) in subject.iter_mut().zip(nonce.to_le_bytes()) {
+                    *s ^= n;
+                }
+                *nonce = nonce.wrapping_add(1);
+                let (random, _) = exec::random(subject).expect("Error in getting random number");
+                u64::from_le_bytes(random[..8].try_into().expect("Slice of 8 bytes"))
+            }
+            Self::Seeded { state } => {
+                let value = mix(*state);
+                *state = state.wrapping_add(GOLDEN_GAMMA);
+                value
+            }
+        }
+    }
+
+    /// Returns a uniformly distributed index in `0..range`.
+    ///
+    /// Draws below `2^64 mod range` are rejected, so that every index is
+    /// backed by the same number of `u64` values and there is no modulo bias.
+    fn gen_index(&mut self, user: ActorId, range: u64) -> u64 {
+        assert!(range > 0, "The range is empty");
+        let threshold = range.wrapping_neg() % range;
+        loop {
+            let value = self.next_u64(user);
+            if value >= threshold {
+                return value % range;
+            }
+        }
+    }
+}
 
 #[derive(Default)]
 struct Wordle {
-    games: HashMap<ActorId, String>,
+    admin: ActorId,
+    trusted_programs: HashSet<ActorId>,
+    rng: Rng,
+    max_attempts: u8,
+    /// Games in progress.
+    games: HashMap<ActorId, Game>,
+    /// The last finished game of each user.
+    finished: HashMap<ActorId, Game>,
+    /// The last period in which each user started the daily puzzle.
+    daily_players: HashMap<ActorId, u64>,
+    /// Statistics of the current and the previous period only.
+    daily_stats: BTreeMap<u64, DailyStats>,
+    /// Answers of the shared games, by caller and `shared_id`.
+    shared_words: HashMap<(ActorId, u64), String>,
+}
+
+impl Wordle {
+    fn assert_admin(&self) {
+        assert_eq!(msg::source(), self.admin, "Only the admin can do this");
+    }
+
+    fn assert_can_act_for(&self, user: ActorId) {
+        let source = msg::source();
+        assert!(
+            source == user || self.trusted_programs.contains(&source),
+            "The caller is not allowed to act for this user"
+        );
+    }
+
+    fn start_game(&mut self, user: ActorId, game: Game) {
+        // A new game replaces the one in progress, which counts as lost
+        if let Some(mut previous) = self.games.insert(user, game) {
+            previous.status = GameStatus::Lost;
+            self.finished.insert(user, previous);
+        }
+    }
+
+    fn finish_game(&mut self, user: ActorId, status: GameStatus) -> &Game {
+        let mut game = self
+            .games
+            .remove(&user)
+            .expect("There is no game with this user");
+        game.status = status;
+        self.finished.insert(user, game);
+        &self.finished[&user]
+    }
+
+    /// The answer of a shared game. The first user draws the word, the others
+    /// get the same one.
+    fn shared_word(&mut self, user: ActorId, owner: ActorId, shared_id: u64) -> String {
+        let rng = &mut self.rng;
+        self.shared_words
+            .entry((owner, shared_id))
+            .or_insert_with(|| {
+                let random_id = rng.gen_index(user, BANK_OF_WORDS.len() as u64);
+                BANK_OF_WORDS[random_id as usize].to_string()
+            })
+            .clone()
+    }
+
+    fn start_daily_game(&mut self, user: ActorId, period: u64) -> String {
+        if self.daily_players.get(&user) == Some(&period) {
+            panic!("The daily puzzle has already been played in this period");
+        }
+        self.daily_players.insert(user, period);
+        self.daily_stats.entry(period).or_default().players += 1;
+        self.daily_stats.retain(|p, _| p + 1 >= period);
+        daily_word(period).to_string()
+    }
+
+    fn record_solved(&mut self, daily_period: Option<u64>) {
+        if let Some(stats) = daily_period.and_then(|p| self.daily_stats.get_mut(&p)) {
+            stats.solved += 1;
+        }
+    }
+
+    fn daily_info(&self, period: u64) -> DailyInfo {
+        let stats_of = |p| self.daily_stats.get(&p).cloned().unwrap_or_default();
+        DailyInfo {
+            period,
+            stats: stats_of(period),
+            previous: period.checked_sub(1).map(|previous| DailyResult {
+                period: previous,
+                answer: daily_word(previous).to_string(),
+                stats: stats_of(previous),
+            }),
+        }
+    }
 }
 
 #[no_mangle]
 extern "C" fn init() {
+    let init: WordleInit = msg::load().expect("Unable to decode `WordleInit`");
+    assert!(init.max_attempts > 0, "Invalid max_attempts");
+    let rng = match init.seed {
+        #[cfg(feature = "test-rng")]
+        Some(seed) => Rng::Seeded { state: seed },
+        #[cfg(not(feature = "test-rng"))]
+        Some(_) => panic!("Seeded words are only available in test-rng builds"),
+        None => Rng::default(),
+    };
     unsafe {
         WORDLE = Some(Wordle {
-            games: HashMap::new(),
-        });
-    }
+            admin: msg::source(),
+            trusted_programs: init.trusted_programs.into_iter().collect(),
+            rng,
+            max_attempts: init.max_attempts,
+            ..Default::default()
+        })
+    };
 }
 
 #[no_mangle]
@@ -27,47 +247,173 @@ extern "C" fn handle() {
 
     let reply = match action {
         Action::StartGame { user } => {
-            let random_id = get_random_value(BANK_OF_WORDS.len() as u8);
+            wordle.assert_can_act_for(user);
+            let random_id = wordle.rng.gen_index(user, BANK_OF_WORDS.len() as u64);
             let word = BANK_OF_WORDS[random_id as usize];
-            wordle.games.insert(user, word.to_string());
+            wordle.start_game(user, Game::new(word.to_string(), None));
+            Event::GameStarted { user }
+        }
+        Action::StartSharedGame { user, shared_id } => {
+            wordle.assert_can_act_for(user);
+            let word = wordle.shared_word(user, msg::source(), shared_id);
+            wordle.start_game(user, Game::new_shared(word));
+            Event::GameStarted { user }
+        }
+        Action::StartSharedGameOf {
+            user,
+            owner,
+            shared_id,
+        } => {
+            assert!(
+                wordle.trusted_programs.contains(&msg::source()),
+                "Only a trusted program can join the games of another caller"
+            );
+            let word = wordle.shared_word(user, owner, shared_id);
+            wordle.start_game(user, Game::new_shared(word));
+            Event::GameStarted { user }
+        }
+        Action::StartGameWithWord { user, word } => {
+            assert!(
+                wordle.trusted_programs.contains(&msg::source()),
+                "Only a trusted program can choose the word"
+            );
+            assert!(
+                BANK_OF_WORDS.contains(&word.as_str()),
+                "The word is not in the dictionary"
+            );
+            wordle.start_game(user, Game::new(word, None));
+            Event::GameStarted { user }
+        }
+        Action::StartDailyGame { user } => {
+            wordle.assert_can_act_for(user);
+            let period = current_period();
+            let word = wordle.start_daily_game(user, period);
+            wordle.start_game(user, Game::new(word, Some(period)));
             Event::GameStarted { user }
         }
         Action::CheckWord { user, word } => {
+            wordle.assert_can_act_for(user);
             if word.len() != 5 {
                 panic!("The length of the word exceeds 5");
             }
-            let key_word = wordle
+            let game = wordle
                 .games
-                .get(&user)
+                .get_mut(&user)
                 .expect("There is no game with this user");
-            let mut matched_indices = Vec::with_capacity(5);
-            let mut key_indices = Vec::with_capacity(5);
-            for (i, (a, b)) in key_word.chars().zip(word.chars()).enumerate() {
-                if a == b {
-                    matched_indices.push(i as u8);
-                } else if key_word.contains(b) {
-                    key_indices.push(i as u8);
+            let guess = game.check(word);
+            let attempts = game.guesses.len();
+
+            if guess.correct_positions.len() == 5 {
+                let daily_period = wordle.finish_game(user, GameStatus::Won).daily_period;
+                wordle.record_solved(daily_period);
+                Event::GameWon { user }
+            } else if attempts >= wordle.max_attempts as usize {
+                let answer = wordle.finish_game(user, GameStatus::Lost).word.clone();
+                Event::GameLost {
+                    user,
+                    correct_positions: guess.correct_positions,
+                    contained_in_word: guess.contained_in_word,
+                    answer,
+                }
+            } else {
+                Event::WordChecked {
+                    user,
+                    correct_positions: guess.correct_positions,
+                    contained_in_word: guess.contained_in_word,
                 }
             }
-
-            Event::WordChecked {
-                user,
-                correct_positions: matched_indices,
-                contained_in_word: key_indices,
+        }
+        Action::AddTrustedProgram { program } => {
+            wordle.assert_admin();
+            wordle.trusted_programs.insert(program);
+            Event::TrustedProgramAdded { program }
+        }
+        Action::RemoveTrustedProgram { program } => {
+            wordle.assert_admin();
+            wordle.trusted_programs.remove(&program);
+            Event::TrustedProgramRemoved { program }
+        }
+        Action::Reveal { user } => {
+            wordle.assert_can_act_for(user);
+            assert!(
+                !wordle.games.contains_key(&user),
+                "The game is still in progress"
+            );
+            let answer = wordle
+                .finished
+                .get(&user)
+                .expect("There is no finished game with this user")
+                .word
+                .clone();
+            Event::Revealed { user, answer }
+        }
+        Action::EndGame { user } => {
+            assert!(
+                wordle.trusted_programs.contains(&msg::source()),
+                "Only a trusted program can end a game"
+            );
+            // A proxy gives up the game for its user, e.g. when the session has ended
+            wordle.finish_game(user, GameStatus::Lost);
+            Event::GameEnded { user }
+        }
+        Action::Forget { users, shared_ids } => {
+            for user in users {
+                wordle.assert_can_act_for(user);
+                wordle.finished.remove(&user);
+            }
+            for shared_id in shared_ids {
+                wordle.shared_words.remove(&(msg::source(), shared_id));
             }
+            Event::Forgotten
         }
     };
 
     msg::reply(reply, 0).expect("Error in sending a reply");
 }
 
-static mut SEED: u8 = 0;
+#[no_mangle]
+extern "C" fn state() {
+    let query: StateQuery = msg::load().expect("Unable to decode `StateQuery`");
+    let wordle = unsafe { WORDLE.as_ref().expect("The program is not initialized") };
+
+    let reply = match query {
+        StateQuery::Daily => StateReply::Daily(wordle.daily_info(current_period())),
+        StateQuery::Players => StateReply::Players(wordle.games.keys().copied().collect()),
+        StateQuery::GameCount => StateReply::GameCount(wordle.games.len() as u32),
+        StateQuery::DictionarySize => StateReply::DictionarySize(BANK_OF_WORDS.len() as u32),
+        StateQuery::GuessHistory(user) => StateReply::GuessHistory(
+            wordle
+                .games
+                .get(&user)
+                .or_else(|| wordle.finished.get(&user))
+                .map(Game::info),
+        ),
+        StateQuery::TrustedPrograms => {
+            StateReply::TrustedPrograms(wordle.trusted_programs.iter().copied().collect())
+        }
+    };
+
+    msg::reply(reply, 0).expect("Unable to share the state");
+}
+
+fn current_period() -> u64 {
+    exec::block_timestamp() / DAILY_PERIOD_MS
+}
+
+/// Picks the shared answer of a period. The choice only depends on the
+/// period, so it is the same for every user and can be revealed later.
+fn daily_word(period: u64) -> &'static str {
+    let mut rng = Rng::Seeded { state: period };
+    let index = rng.gen_index(ActorId::zero(), BANK_OF_WORDS.len() as u64);
+    BANK_OF_WORDS[index as usize]
+}
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
 
-pub fn get_random_value(range: u8) -> u8 {
-    let seed = unsafe { SEED };
-    unsafe { SEED = SEED.wrapping_add(1) };
-    let mut random_input: [u8; 32] = exec::program_id().into();
-    random_input[0] = random_input[0].wrapping_add(seed);
-    let (random, _) = exec::random(random_input).expect("Error in getting random number");
-    random[0] % range
+/// The output function of SplitMix64, spreads consecutive inputs over the whole range.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(GOLDEN_GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
@@ -0,0 +1,606 @@
+use gstd::ActorId;
+use gtest::{Log, Program, ProgramBuilder, System};
+use wordle_io::*;
+
+const WORDLE_PROGRAM_ID: u64 = 2;
+const USER: u64 = 50;
+const OTHER_USER: u64 = 51;
+const PROXY: u64 = 60;
+// The first word drawn with this seed is "horse"
+const SEED: u64 = 1;
+const MAX_ATTEMPTS: u8 = 5;
+
+fn deploy(system: &System, seed: Option<u64>) -> Program<'_> {
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(system);
+    let res = wordle_program.send(
+        USER,
+        WordleInit {
+            seed,
+            max_attempts: MAX_ATTEMPTS,
+            trusted_programs: vec![PROXY.into()],
+        },
+    );
+    assert!(!res.main_failed());
+    wordle_program
+}
+
+fn daily_info(wordle_program: &Program) -> DailyInfo {
+    let StateReply::Daily(info) = wordle_program.read_state(StateQuery::Daily).unwrap() else {
+        panic!("Unexpected state reply");
+    };
+    info
+}
+
+fn guess_history(wordle_program: &Program, user: u64) -> Option<GameInfo> {
+    let StateReply::GuessHistory(info) = wordle_program
+        .read_state(StateQuery::GuessHistory(user.into()))
+        .unwrap()
+    else {
+        panic!("Unexpected state reply");
+    };
+    info
+}
+
+#[test]
+fn test_daily_game() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, None);
+
+    let res = wordle_program.send(USER, Action::StartDailyGame { user: USER.into() });
+    assert!(!res.main_failed());
+
+    // The daily puzzle can be played only once per period
+    let res = wordle_program.send(USER, Action::StartDailyGame { user: USER.into() });
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(
+        OTHER_USER,
+        Action::StartDailyGame {
+            user: OTHER_USER.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Both users share the same answer, so the same guess gets the same result.
+    // "hours" is not in the dictionary, so the guess never finishes the game.
+    let check = |user: u64| {
+        let res = wordle_program.send(
+            user,
+            Action::CheckWord {
+                user: user.into(),
+                word: "hours".to_string(),
+            },
+        );
+        assert!(!res.main_failed());
+        res.decoded_log::<Event>()
+            .into_iter()
+            .find_map(|log| match log.payload() {
+                Event::WordChecked {
+                    correct_positions,
+                    contained_in_word,
+                    ..
+                } => Some((correct_positions.clone(), contained_in_word.clone())),
+                _ => None,
+            })
+            .expect("No `WordChecked` reply")
+    };
+    assert_eq!(check(USER), check(OTHER_USER));
+
+    let info = daily_info(&wordle_program);
+    assert_eq!(info.stats.players, 2);
+    assert_eq!(info.previous.is_some(), info.period > 0);
+
+    // The answer is one of the dictionary words, trying them solves the puzzle
+    let answer = BANK_OF_WORDS
+        .iter()
+        .find(|word| {
+            let res = wordle_program.send(
+                USER,
+                Action::CheckWord {
+                    user: USER.into(),
+                    word: word.to_string(),
+                },
+            );
+            assert!(!res.main_failed());
+            let log = Log::builder()
+                .dest(USER)
+                .source(WORDLE_PROGRAM_ID)
+                .payload(Event::GameWon { user: USER.into() });
+            res.contains(&log)
+        })
+        .expect("The puzzle was not solved");
+    let solved = DailyStats {
+        players: 2,
+        solved: 1,
+    };
+    assert_eq!(daily_info(&wordle_program).stats, solved);
+
+    // In the next period the stats start over and the answer is revealed
+    let period = info.period;
+    while daily_info(&wordle_program).period == period {
+        system.spend_blocks(1_000);
+    }
+    let info = daily_info(&wordle_program);
+    assert_eq!(info.period, period + 1);
+    assert_eq!(info.stats, DailyStats::default());
+    let previous = info.previous.expect("The previous period is missing");
+    assert_eq!(previous.period, period);
+    assert_eq!(previous.answer, *answer);
+    assert_eq!(previous.stats, solved);
+
+    let res = wordle_program.send(USER, Action::StartDailyGame { user: USER.into() });
+    assert!(!res.main_failed());
+    assert_eq!(daily_info(&wordle_program).stats.players, 1);
+}
+
+#[test]
+fn test_seeded_game() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    let res = wordle_program.send(USER, Action::StartGame { user: USER.into() });
+    assert!(!res.main_failed());
+
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "horse".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::GameWon { user: USER.into() });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // The game is over
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "horse".to_string(),
+        },
+    );
+    assert!(res.main_failed());
+}
+
+#[test]
+fn test_game_lost() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    let res = wordle_program.send(USER, Action::StartGame { user: USER.into() });
+    assert!(!res.main_failed());
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let res = wordle_program.send(
+            USER,
+            Action::CheckWord {
+                user: USER.into(),
+                word: "house".to_string(),
+            },
+        );
+        let event = if attempt == MAX_ATTEMPTS {
+            Event::GameLost {
+                user: USER.into(),
+                correct_positions: vec![0, 1, 3, 4],
+                contained_in_word: vec![],
+                answer: "horse".to_string(),
+            }
+        } else {
+            Event::WordChecked {
+                user: USER.into(),
+                correct_positions: vec![0, 1, 3, 4],
+                contained_in_word: vec![],
+            }
+        };
+        let log = Log::builder()
+            .dest(USER)
+            .source(WORDLE_PROGRAM_ID)
+            .payload(event);
+        assert!(!res.main_failed() && res.contains(&log));
+    }
+
+    let count: StateReply = wordle_program.read_state(StateQuery::GameCount).unwrap();
+    assert!(matches!(count, StateReply::GameCount(0)));
+    let info = guess_history(&wordle_program, USER).expect("No game");
+    assert_eq!(info.status, GameStatus::Lost);
+    assert_eq!(info.guesses.len(), MAX_ATTEMPTS as usize);
+}
+
+#[test]
+fn test_state_queries() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    let res = wordle_program.send(USER, Action::StartGame { user: USER.into() });
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "house".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    let players: StateReply = wordle_program.read_state(StateQuery::Players).unwrap();
+    assert!(matches!(players, StateReply::Players(users) if users == vec![ActorId::from(USER)]));
+    let count: StateReply = wordle_program.read_state(StateQuery::GameCount).unwrap();
+    assert!(matches!(count, StateReply::GameCount(1)));
+    let size: StateReply = wordle_program
+        .read_state(StateQuery::DictionarySize)
+        .unwrap();
+    assert!(matches!(size, StateReply::DictionarySize(3)));
+
+    // The secret word stays hidden while the game is in progress
+    let info = guess_history(&wordle_program, USER).expect("No game");
+    assert_eq!(info.word, None);
+    assert_eq!(
+        info.guesses,
+        vec![Guess {
+            word: "house".to_string(),
+            correct_positions: vec![0, 1, 3, 4],
+            contained_in_word: vec![],
+        }]
+    );
+
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "horse".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    let info = guess_history(&wordle_program, USER).expect("No game");
+    assert_eq!(info.status, GameStatus::Won);
+    assert_eq!(info.word.as_deref(), Some("horse"));
+    assert!(guess_history(&wordle_program, OTHER_USER).is_none());
+}
+
+#[test]
+fn test_access_control() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    // Nobody else can start or probe a user's game
+    let res = wordle_program.send(OTHER_USER, Action::StartGame { user: USER.into() });
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(PROXY, Action::StartGame { user: USER.into() });
+    assert!(!res.main_failed());
+
+    let res = wordle_program.send(
+        OTHER_USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "house".to_string(),
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "house".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Only the admin manages the trusted programs
+    let res = wordle_program.send(
+        OTHER_USER,
+        Action::RemoveTrustedProgram {
+            program: PROXY.into(),
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(
+        USER,
+        Action::RemoveTrustedProgram {
+            program: PROXY.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    let res = wordle_program.send(PROXY, Action::StartGame { user: USER.into() });
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(
+        USER,
+        Action::AddTrustedProgram {
+            program: OTHER_USER.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    let StateReply::TrustedPrograms(programs) = wordle_program
+        .read_state(StateQuery::TrustedPrograms)
+        .unwrap()
+    else {
+        panic!("Unexpected state reply");
+    };
+    assert_eq!(programs, vec![ActorId::from(OTHER_USER)]);
+}
+
+#[test]
+fn test_reveal() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    let res = wordle_program.send(USER, Action::StartGame { user: USER.into() });
+    assert!(!res.main_failed());
+
+    // The user cannot look up the answer of a running game
+    let res = wordle_program.send(USER, Action::Reveal { user: USER.into() });
+    assert!(res.main_failed());
+
+    // Neither can a trusted program, it has to end the game first
+    let res = wordle_program.send(PROXY, Action::Reveal { user: USER.into() });
+    assert!(res.main_failed());
+
+    // Only a trusted program may end the game of another user
+    let res = wordle_program.send(OTHER_USER, Action::EndGame { user: USER.into() });
+    assert!(res.main_failed());
+    let res = wordle_program.send(PROXY, Action::EndGame { user: USER.into() });
+    let log = Log::builder()
+        .dest(PROXY)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::GameEnded { user: USER.into() });
+    assert!(!res.main_failed() && res.contains(&log));
+    let info = guess_history(&wordle_program, USER).expect("The game is missing");
+    assert_eq!(info.status, GameStatus::Lost);
+
+    let res = wordle_program.send(PROXY, Action::Reveal { user: USER.into() });
+    let log = Log::builder()
+        .dest(PROXY)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::Revealed {
+            user: USER.into(),
+            answer: "horse".to_string(),
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // Once the game is over, the user may reveal it too
+    let res = wordle_program.send(USER, Action::Reveal { user: USER.into() });
+    let log = Log::builder()
+        .dest(USER)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::Revealed {
+            user: USER.into(),
+            answer: "horse".to_string(),
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    let res = wordle_program.send(OTHER_USER, Action::Reveal { user: USER.into() });
+    assert!(res.main_failed());
+}
+
+#[test]
+fn test_shared_game() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    for user in [USER, OTHER_USER] {
+        let res = wordle_program.send(
+            PROXY,
+            Action::StartSharedGame {
+                user: user.into(),
+                shared_id: 7,
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    // Both users got the first word drawn
+    for user in [USER, OTHER_USER] {
+        let res = wordle_program.send(
+            user,
+            Action::CheckWord {
+                user: user.into(),
+                word: "horse".to_string(),
+            },
+        );
+        let log = Log::builder()
+            .dest(user)
+            .source(WORDLE_PROGRAM_ID)
+            .payload(Event::GameWon { user: user.into() });
+        assert!(!res.main_failed() && res.contains(&log));
+    }
+
+    // The answer stays out of the state, other users may still be playing it
+    let info = guess_history(&wordle_program, USER).expect("The game is missing");
+    assert_eq!(info.status, GameStatus::Won);
+    assert_eq!(info.word, None);
+
+    // The same id of another caller is another game
+    let res = wordle_program.send(
+        USER,
+        Action::StartSharedGame {
+            user: USER.into(),
+            shared_id: 7,
+        },
+    );
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "human".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::GameWon { user: USER.into() });
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // A trusted program can join the shared games of another caller
+    let res = wordle_program.send(
+        USER,
+        Action::StartSharedGameOf {
+            user: USER.into(),
+            owner: PROXY.into(),
+            shared_id: 7,
+        },
+    );
+    assert!(res.main_failed());
+    let res = wordle_program.send(
+        PROXY,
+        Action::StartSharedGameOf {
+            user: OTHER_USER.into(),
+            owner: USER.into(),
+            shared_id: 7,
+        },
+    );
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        OTHER_USER,
+        Action::CheckWord {
+            user: OTHER_USER.into(),
+            word: "human".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(OTHER_USER)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::GameWon {
+            user: OTHER_USER.into(),
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+}
+
+#[test]
+fn test_game_with_word() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    // Users cannot choose their own word
+    let res = wordle_program.send(
+        USER,
+        Action::StartGameWithWord {
+            user: USER.into(),
+            word: "human".to_string(),
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(
+        PROXY,
+        Action::StartGameWithWord {
+            user: USER.into(),
+            word: "abcde".to_string(),
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(
+        PROXY,
+        Action::StartGameWithWord {
+            user: USER.into(),
+            word: "human".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "human".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::GameWon { user: USER.into() });
+    assert!(!res.main_failed() && res.contains(&log));
+}
+
+#[test]
+fn test_forget() {
+    let system = System::new();
+    system.init_logger();
+    let wordle_program = deploy(&system, Some(SEED));
+
+    let res = wordle_program.send(
+        PROXY,
+        Action::StartSharedGame {
+            user: USER.into(),
+            shared_id: 7,
+        },
+    );
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        Action::CheckWord {
+            user: USER.into(),
+            word: "horse".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    assert!(guess_history(&wordle_program, USER).is_some());
+
+    // Only the user or a trusted program can forget a user's game
+    let res = wordle_program.send(
+        OTHER_USER,
+        Action::Forget {
+            users: vec![USER.into()],
+            shared_ids: vec![],
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = wordle_program.send(
+        PROXY,
+        Action::Forget {
+            users: vec![USER.into()],
+            shared_ids: vec![7],
+        },
+    );
+    let log = Log::builder()
+        .dest(PROXY)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::Forgotten);
+    assert!(!res.main_failed() && res.contains(&log));
+    assert!(guess_history(&wordle_program, USER).is_none());
+
+    // The shared id draws a new word
+    let res = wordle_program.send(
+        PROXY,
+        Action::StartSharedGame {
+            user: OTHER_USER.into(),
+            shared_id: 7,
+        },
+    );
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        OTHER_USER,
+        Action::CheckWord {
+            user: OTHER_USER.into(),
+            word: "human".to_string(),
+        },
+    );
+    let log = Log::builder()
+        .dest(OTHER_USER)
+        .source(WORDLE_PROGRAM_ID)
+        .payload(Event::GameWon {
+            user: OTHER_USER.into(),
+        });
+    assert!(!res.main_failed() && res.contains(&log));
+}